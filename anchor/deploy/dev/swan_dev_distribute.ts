@@ -55,7 +55,9 @@ const IDL = require("../../target/idl/swan.json");
       .distribute()
       .accounts({
         state: statePda,
+        payer: beneficiary.publicKey,
         beneficiary: beneficiary.publicKey,
+        feeRecipient: beneficiary.publicKey,
       })
       .signers([beneficiary])
       .transaction();
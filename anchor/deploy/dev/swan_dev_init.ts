@@ -50,7 +50,9 @@ const programDeployerFile = path.resolve(__dirname, './programDeployerKeyPair.js
         .init(
           tokenProvider.publicKey,  // Token Provider
           beneficiary.publicKey, // Beneficiary
-          safeguard.publicKey // Safeguarding Account
+          safeguard.publicKey, // Safeguarding Account
+          0, // Platform fee in basis points
+          beneficiary.publicKey // Fee Recipient
         )
         .accounts({
           signer: tokenProvider.publicKey
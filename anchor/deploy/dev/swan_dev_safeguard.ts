@@ -2,6 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { clusterApiUrl, Connection, PublicKey } from "@solana/web3.js";
 import { Swan } from "../../target/types/swan";
 import { getKeypairFromFile } from "@solana-developers/helpers";
+import { getSwanRefundReserve } from "../../src/swan-exports";
 import path from "path";
 
 const IDL = require("../../target/idl/swan.json");
@@ -46,9 +47,15 @@ const IDL = require("../../target/idl/swan.json");
   );
   console.log("State PDA:", statePda.toString());
 
+  // Safeguard everything except the rent-exempt balance and the amounts reserved for refunds
+  const stateInfo = await connection.getAccountInfo(statePda);
+  const rentExempt = await connection.getMinimumBalanceForRentExemption(stateInfo!.data.length);
+  const stateAccountBefore = await swanProgram.account.state.fetch(statePda);
+  const amount = new anchor.BN(stateInfo!.lamports - rentExempt).sub(getSwanRefundReserve(stateAccountBefore));
+
   try {
     await swanProgram.methods
-      .safeguard()
+      .safeguard(amount)
       .accountsPartial({
         state: statePda,
         tokenProvider: tokenProvider.publicKey,
@@ -1,7 +1,11 @@
+import * as anchor from "@coral-xyz/anchor";
 import { getKeypairFromFile } from '@solana-developers/helpers';
 import { Connection, Keypair, PublicKey, Transaction, SystemProgram, clusterApiUrl } from '@solana/web3.js';
 import fs from 'fs';
 import path from 'path';
+import { Swan } from "../../target/types/swan";
+
+const IDL = require("../../target/idl/swan.json");
 
 // Constants
 const TARGET_ADDRESS = '8apswKiMCUXnhj2vR4rktzoZX5biupNbzuwkauwNbHpz'; // statepda
@@ -34,22 +38,21 @@ const SOL_TO_SEND = 4.49758488; // Amount of SOL to send
 
     console.log(`Amount to send: ${lamportsToSend} lamports (${SOL_TO_SEND} SOL)`);
 
-    // Create the transaction
-    const transaction = new Transaction().add(
-      SystemProgram.transfer({
-        fromPubkey: senderPublicKey,
-        toPubkey: new PublicKey(TARGET_ADDRESS),
-        lamports: lamportsToSend,
+    const provider = new anchor.AnchorProvider(connection, new anchor.Wallet(senderKeypair), {
+      preflightCommitment: "confirmed",
+    });
+    const swanProgram: anchor.Program<Swan> = new anchor.Program(IDL, provider);
+
+    // Return the funds through the program so the state records them
+    const signature = await swanProgram.methods
+      .returnFromSafeguard(new anchor.BN(lamportsToSend))
+      .accounts({
+        state: new PublicKey(TARGET_ADDRESS),
+        safeguardingAccount: senderPublicKey,
       })
-    );
-
-    // Sign and send the transaction
-    const signature = await connection.sendTransaction(transaction, [senderKeypair]);
-    console.log(`Transaction sent with signature: ${signature}`);
-
-    // Confirm the transaction
-    const confirmation = await connection.confirmTransaction(signature, 'confirmed');
-    console.log('Transaction confirmed:', confirmation);
+      .signers([senderKeypair])
+      .rpc();
+    console.log(`Transaction confirmed with signature: ${signature}`);
 
     console.log(`Successfully transferred ${SOL_TO_SEND} SOL (${lamportsToSend} lamports) to ${TARGET_ADDRESS}`);
   } catch (error) {
@@ -0,0 +1,62 @@
+import * as anchor from "@coral-xyz/anchor";
+import {
+  clusterApiUrl,
+  Connection,
+  PublicKey,
+} from "@solana/web3.js";
+import { Swan } from "../../target/types/swan";
+import {getKeypairFromFile} from "@solana-developers/helpers"
+import path from 'path';
+import fs from 'fs';
+import { createHash } from 'crypto';
+
+const IDL = require("../../target/idl/swan.json");
+
+const WHITE_PAPER_URI = "https://example.com/white-paper.pdf";
+
+(async () => {
+let swanProgram: anchor.Program<Swan>;
+let tokenProvider: any;
+
+    const tokenProviderFile = path.resolve(__dirname, './tokenProviderKeyPair.json');
+    tokenProvider = await getKeypairFromFile(tokenProviderFile);
+
+    const whitePaperFile = path.resolve(__dirname, './white_paper.pdf');
+    const whitePaperHash = Array.from(createHash('sha256').update(fs.readFileSync(whitePaperFile)).digest());
+
+    const connection = new Connection(clusterApiUrl('devnet'), 'confirmed');  // Devnet connection
+    const provider = new anchor.AnchorProvider(connection, new anchor.Wallet(tokenProvider), {
+      preflightCommitment: "confirmed",
+    });
+
+    swanProgram = new anchor.Program(IDL, provider);
+
+    const [statePda, stateBump] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state")],
+      swanProgram.programId
+    );
+
+    // Each publication appends a new version entry
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    const version = Buffer.alloc(2);
+    version.writeUInt16LE(stateAccount.whitePaperVersion + 1);
+    const [whitePaperPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("white_paper"), version],
+      swanProgram.programId
+    );
+
+    await swanProgram.methods
+      .publishWhitePaper(
+        whitePaperHash,
+        WHITE_PAPER_URI,
+      )
+      .accountsPartial({
+        state: statePda,
+        tokenProvider: tokenProvider.publicKey,
+        whitePaper: whitePaperPda,
+      })
+      .signers([tokenProvider])
+      .rpc();
+
+    console.log('White paper published; version:', stateAccount.whitePaperVersion + 1);
+})();
@@ -49,7 +49,9 @@ const tokenProviderPubKey = new PublicKey("HRfEvBeQ4VDh6Z3mjJTgrE14htcYcFDZXkyeg
       .init(
         tokenProviderPubKey, // Token Provider
         beneficiary, // Beneficiary
-        safeguard // Safeguarding Account
+        safeguard, // Safeguarding Account
+        0, // Platform fee in basis points
+        beneficiary // Fee Recipient
       )
       .accounts({
         signer: programDeployer.publicKey,
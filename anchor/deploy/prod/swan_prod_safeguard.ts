@@ -2,6 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { clusterApiUrl, Connection, PublicKey } from "@solana/web3.js";
 import { Swan } from "../../target/types/swan";
 import { getKeypairFromFile } from "@solana-developers/helpers";
+import { getSwanRefundReserve } from "../../src/swan-exports";
 import path from "path";
 
 const IDL = require("../../target/idl/swan.json");
@@ -45,13 +46,19 @@ const IDL = require("../../target/idl/swan.json");
     throw new Error("Unexpected State PDA address");
   }
 
+  // Safeguard everything except the rent-exempt balance and the amounts reserved for refunds
+  const stateInfo = await connection.getAccountInfo(statePda);
+  const rentExempt = await connection.getMinimumBalanceForRentExemption(stateInfo!.data.length);
+  const stateAccountBefore = await swanProgram.account.state.fetch(statePda);
+  const amount = new anchor.BN(stateInfo!.lamports - rentExempt).sub(getSwanRefundReserve(stateAccountBefore));
+
   try {
     const addPriorityFee = anchor.web3.ComputeBudgetProgram.setComputeUnitPrice({
       microLamports: 5_000_000
     });
 
     const tx = await swanProgram.methods
-      .safeguard()
+      .safeguard(amount)
       .accounts({
         state: statePda,
         tokenProvider: tokenProvider.publicKey,
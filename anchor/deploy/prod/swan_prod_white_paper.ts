@@ -0,0 +1,103 @@
+import * as anchor from "@coral-xyz/anchor";
+import { clusterApiUrl, Connection, PublicKey } from "@solana/web3.js";
+import { Swan } from "../../target/types/swan";
+import { getKeypairFromFile } from "@solana-developers/helpers";
+import path from "path";
+import fs from "fs";
+import { createHash } from "crypto";
+import * as readline from 'readline';
+
+const IDL = require("../../target/idl/swan.json");
+
+// Usage: swan_prod_white_paper.ts <white paper uri>, with the published document next to this script
+const WHITE_PAPER_URI = process.argv[2];
+
+// Add confirmation prompt
+function askForConfirmation(summary: string): Promise<boolean> {
+  const rl = readline.createInterface({
+    input: process.stdin,
+    output: process.stdout
+  });
+
+  return new Promise(resolve => {
+    rl.question(`${summary}\nPublish this white paper? (y/n) `, answer => {
+      rl.close();
+      resolve(answer.toLowerCase() === 'y');
+    });
+  });
+}
+
+(async () => {
+  let swanProgram: anchor.Program<Swan>;
+
+  if (!WHITE_PAPER_URI) {
+    throw new Error("Missing white paper URI");
+  }
+
+  const tokenProviderFile = path.resolve(
+    __dirname,
+    "./tokenProviderKeyPair.json"
+  );
+  const tokenProvider = await getKeypairFromFile(tokenProviderFile);
+
+  const whitePaperFile = path.resolve(__dirname, "./white_paper.pdf");
+  const whitePaperHash = createHash("sha256").update(fs.readFileSync(whitePaperFile)).digest();
+
+  const connection = new Connection(clusterApiUrl("mainnet-beta"), "confirmed");
+  const provider = new anchor.AnchorProvider(
+    connection,
+    new anchor.Wallet(tokenProvider),
+    {
+      preflightCommitment: "confirmed",
+    }
+  );
+
+  swanProgram = new anchor.Program(IDL, provider);
+
+  const [statePda, stateBump] = PublicKey.findProgramAddressSync(
+    [Buffer.from("state")],
+    swanProgram.programId
+  );
+
+  console.log("State PDA is: ", statePda);
+  if (!statePda.equals(new PublicKey("8yNEGapcYsShRedngtaVHPAJy5ujhHeY2KuKrR1zzfRZ"))) {
+    throw new Error("Unexpected State PDA address");
+  }
+
+  // Each publication appends a new version entry, which participants must accept from then on
+  const stateAccount = await swanProgram.account.state.fetch(statePda);
+  const nextVersion = stateAccount.whitePaperVersion + 1;
+  const version = Buffer.alloc(2);
+  version.writeUInt16LE(nextVersion);
+  const [whitePaperPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("white_paper"), version],
+    swanProgram.programId
+  );
+
+  const confirmed = await askForConfirmation(
+    `Version: ${nextVersion}\nSHA-256: ${whitePaperHash.toString("hex")}\nURI: ${WHITE_PAPER_URI}`
+  );
+  if (!confirmed) {
+    console.log("Publication cancelled");
+    return;
+  }
+
+  try {
+    await swanProgram.methods
+      .publishWhitePaper(
+        Array.from(whitePaperHash),
+        WHITE_PAPER_URI,
+      )
+      .accountsPartial({
+        state: statePda,
+        tokenProvider: tokenProvider.publicKey,
+        whitePaper: whitePaperPda,
+      })
+      .signers([tokenProvider])
+      .rpc();
+
+    console.log("White paper published; version:", nextVersion);
+  } catch (err) {
+    console.log(err);
+  }
+})();
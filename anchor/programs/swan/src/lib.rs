@@ -1,10 +1,18 @@
 #![allow(clippy::result_large_err)]
 
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 
 use anchor_spl::token::{self, Token, TokenAccount, Mint,Transfer};
+use anchor_spl::associated_token::AssociatedToken;
 use anchor_lang::solana_program::program::invoke;
 use anchor_lang::solana_program::system_instruction;
+use anchor_lang::solana_program::keccak::hashv;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::stake;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::solana_program::stake::state::{Authorized, Lockup, StakeStateV2};
 
 declare_id!("BUYLB52z4smtpLUMosr45FckaC1DhhFL9HHiUMUBNM5m");
 
@@ -17,18 +25,23 @@ pub mod swan {
         token_provider: Pubkey,
         beneficiary: Pubkey,
         safeguarding_account: Pubkey,
+        fee_bps: u16,
+        fee_recipient: Pubkey,
     ) -> Result<()> {
         let state = &mut ctx.accounts.state;
         require!(!state.initialized, CustomError::AlreadyInitialized);
+        require!(fee_bps <= 10_000, CustomError::InvalidFee);
 
         state.token_provider = token_provider;
         state.beneficiary = beneficiary;
         state.safeguarding_account = safeguarding_account;
+        state.fee_bps = fee_bps;
+        state.fee_recipient = fee_recipient;
         state.initialized = true;
         state.raise_cap = 5_000_000_000_000; // Hardcoded raise of 5,000 SOL
 
-        msg!("MiCA-compliant ICO contract initialized; token provider: {}, beneficiary: {}, safeguarding_account: {}, raise cap: {}", 
-          token_provider, beneficiary, safeguarding_account, state.raise_cap);
+        msg!("MiCA-compliant ICO contract initialized; token provider: {}, beneficiary: {}, safeguarding_account: {}, raise cap: {}, fee bps: {}, fee recipient: {}", 
+          token_provider, beneficiary, safeguarding_account, state.raise_cap, fee_bps, fee_recipient);
 
         Ok(())
     }
@@ -60,10 +73,20 @@ pub mod swan {
             ctx.accounts.token_mint.key()
         );
 
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Deposit,
+            ctx.accounts.token_authority.key(),
+            amount,
+        )?;
+
         Ok(())
     }
 
-    pub fn activate(ctx: Context<Activate>, duration: u64) -> Result<()> {
+    // Allow contributions beyond the raise cap; the excess is refunded pro-rata on claim.
+    // Can be called only by the token provider before activation
+    pub fn configure_oversubscription(ctx: Context<Configure>, enabled: bool) -> Result<()> {
         let state = &mut ctx.accounts.state;
         require!(
             ctx.accounts.token_provider.key() == state.token_provider,
@@ -71,313 +94,2311 @@ pub mod swan {
         );
         require!(!state.participation_active, CustomError::AlreadyActivated);
 
-        state.participation_active = true;
-        state.duration = duration;  // Save the duration
-        state.participation_end = Clock::get()?.unix_timestamp as u64 + duration;
+        state.oversubscription_enabled = enabled;
 
-        msg!(
-            "Participation period activated; participation period ends at: {}",
-            state.participation_end
-        );
+        msg!("Oversubscription mode configured; enabled: {}", enabled);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
         Ok(())
     }
 
-    // Initialize a participant's participation account.
-    // Must be called by the participant before participating.
-    pub fn init_participant(ctx: Context<InitParticipant>) -> Result<()> {
-        let mut participant = ctx.accounts.participant_account.load_init()?;
-        participant.participant = ctx.accounts.participant.key();
-        participant.amount = 0;
-        participant.participation_time = 0;
-        participant.cancelled = 0;
-        
+    // Restrict participation to allowlisted wallets until the priority window ends.
+    // Can be called only by the token provider before activation
+    pub fn configure_priority_round(
+        ctx: Context<Configure>,
+        merkle_root: [u8; 32],
+        priority_end: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+
+        state.merkle_root = merkle_root;
+        state.priority_end = priority_end;
+
+        msg!("Priority round configured; priority window ends at: {}", priority_end);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
         Ok(())
     }
 
-    pub fn participate(ctx: Context<Participate>, amount: u64) -> Result<()> {
+    // Split the payout between several beneficiaries by basis-point weights summing to 10,000.
+    // The first entry replaces `beneficiary` and receives any rounding remainder.
+    // Can be called only by the token provider before activation
+    pub fn configure_beneficiaries(
+        ctx: Context<Configure>,
+        beneficiaries: Vec<BeneficiaryShare>,
+    ) -> Result<()> {
         let state = &mut ctx.accounts.state;
-        require!(state.participation_active, CustomError::NotActive);
-        require!(Clock::get()?.unix_timestamp as u64 <= state.participation_end, CustomError::ParticipationClosed);
-        require!(state.total_contributed + amount <= state.raise_cap, CustomError::RaiseCapExceeded);
-
-        let mut participant = ctx.accounts.participant_account.load_mut()?;
-        require!(participant.cancelled != 1, CustomError::ParticipationCancelledAlready);
-        
-        // Add check for maximum investment cap (250 SOL = 250_000_000_000 lamports)
         require!(
-            participant.amount + amount <= 250_000_000_000,
-            CustomError::MaxContributionExceeded
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+        require!(
+            !beneficiaries.is_empty() && beneficiaries.len() <= MAX_BENEFICIARIES,
+            CustomError::InvalidBeneficiarySplit
+        );
+        require!(
+            beneficiaries.iter().map(|share| share.bps as u64).sum::<u64>() == 10_000,
+            CustomError::InvalidBeneficiarySplit
         );
 
-        if participant.amount == 0 {
-            state.unique_investor_count += 1;
-            // Set early investor flag if they're among first 100
-            if state.unique_investor_count <= 100 {
-                participant.is_early_investor = 1;
-                state.active_early_investor_count += 1;
-            }
-        }
+        state.beneficiary = beneficiaries[0].recipient;
+        state.beneficiaries = beneficiaries
+            .into_iter()
+            .map(|share| BeneficiaryShare { paid_lamports: 0, ..share })
+            .collect();
 
-        let was_large_investor = participant.amount >= 100_000_000_000;
-        participant.amount += amount;
-        let is_large_investor = participant.amount >= 100_000_000_000;
+        msg!("Beneficiaries configured; count: {}", state.beneficiaries.len());
 
-        // Update large investor count if status changed
-        if !was_large_investor && is_large_investor {
-            state.large_investor_count += 1;
-        }
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
-        participant.participation_time = Clock::get()?.unix_timestamp as u64;
-        state.total_contributed = state.total_contributed
-            .checked_add(amount)
-            .ok_or(CustomError::ArithmeticOverflow)?;
+        Ok(())
+    }
 
-       // Perform the transfer using the System Program
-    let transfer_instruction = system_instruction::transfer(
-        &ctx.accounts.participant.key(),
-        &ctx.accounts.state.key(),
-        amount,
-    );
+    // Hold the raise in escrow after distribution and release it in tranches approved by the custodian.
+    // Tranche weights must sum to 10,000 basis points.
+    // Can be called only by the token provider before activation
+    pub fn configure_tranches(
+        ctx: Context<Configure>,
+        escrow_custodian: Pubkey,
+        tranches: Vec<Tranche>,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+        require!(
+            !tranches.is_empty() && tranches.len() <= MAX_TRANCHES,
+            CustomError::InvalidTranche
+        );
+        require!(
+            tranches.iter().map(|tranche| tranche.bps as u64).sum::<u64>() == 10_000,
+            CustomError::InvalidTranche
+        );
 
-    invoke(
-        &transfer_instruction,
-        &[
-            ctx.accounts.participant.to_account_info(),
-            ctx.accounts.state.to_account_info(),
-        ],
-    )?;
+        state.escrow_custodian = escrow_custodian;
+        state.tranches = tranches
+            .into_iter()
+            .map(|tranche| Tranche {
+                approved: false,
+                released: false,
+                released_lamports: 0,
+                vote_end: 0,
+                votes_for: 0,
+                votes_against: 0,
+                ..tranche
+            })
+            .collect();
+
+        msg!("Tranches configured; escrow custodian: {}, count: {}", escrow_custodian, state.tranches.len());
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
         Ok(())
     }
 
-    // Cancel participation and signal intent to cancel (must be done within 14 days of participation).
-    // Can be called by the participant
-    pub fn cancel(ctx: Context<Cancel>) -> Result<()> {
+    // Require a contributor-weighted vote before each escrowed tranche is released.
+    // A vote opens when the custodian approves the tranche and lasts `vote_duration` seconds.
+    // Can be called only by the token provider before activation
+    pub fn configure_tranche_vote(ctx: Context<Configure>, vote_duration: u64) -> Result<()> {
         let state = &mut ctx.accounts.state;
-        let mut participant = ctx.accounts.participant_account.load_mut()?;
-        let now = Clock::get()?.unix_timestamp as u64;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
 
-        require!(now <= state.participation_end, CustomError::WithdrawalClosed);
+        state.tranche_vote_duration = vote_duration;
+
+        msg!("Tranche vote configured; vote duration: {}", vote_duration);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Publish the crypto-asset white paper the offer is bound to.
+    // Every call appends a new version entry; after activation only supplements can be published.
+    // Can be called only by the token provider before distribution
+    pub fn publish_white_paper(
+        ctx: Context<PublishWhitePaper>,
+        hash: [u8; 32],
+        uri: String,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
         require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);
-        
-        let amount = participant.amount;
-        require!(amount > 0, CustomError::NoContribution);
-        require!(participant.cancelled == 0, CustomError::AlreadyCancelled);
+        require!(
+            hash != [0u8; 32] && !uri.is_empty() && uri.len() <= MAX_URI_LEN,
+            CustomError::InvalidWhitePaper
+        );
 
-        // Decrease large investor count if applicable
-        if amount >= 100_000_000_000 {
-            state.large_investor_count = state.large_investor_count.saturating_sub(1);
-        }
+        let version = state.white_paper_version + 1;
+        let entry = &mut ctx.accounts.white_paper;
+        entry.version = version;
+        entry.hash = hash;
+        entry.uri = uri.clone();
+        entry.published_at = Clock::get()?.unix_timestamp as u64;
 
-        // Clear early investor flag and decrement active count
-        if participant.is_early_investor == 1 {
-            participant.is_early_investor = 0;
-            state.active_early_investor_count = state.active_early_investor_count.saturating_sub(1);
-        }
+        state.white_paper_version = version;
+        state.white_paper_hash = hash;
+        state.white_paper_uri = uri;
 
-        state.total_cancelled += amount;
-        participant.cancelled = 1;
-        state.total_contributed -= amount;
+        msg!(
+            "White paper published; version: {}, uri: {}, supplement: {}",
+            version,
+            state.white_paper_uri,
+            state.participation_active
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::PublishWhitePaper,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
         Ok(())
     }
 
-    // Moves the SOL to the safeguarding account
-    pub fn safeguard(ctx: Context<Safeguard>) -> Result<()> {
-        let state = &ctx.accounts.state;
-        require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);   // Safeguarding can only be called before the distribution period starts
+    // Cap the offer in EUR, e.g. to stay under a MiCA Article 4 exemption threshold.
+    // Contributions are valued with a Pyth-style SOL/EUR price account; prices older than
+    // `max_staleness` seconds are rejected. Cancellations do not free up EUR headroom.
+    // Can be called only by the token provider before activation
+    pub fn configure_eur_cap(
+        ctx: Context<Configure>,
+        eur_cap_cents: u64,
+        eur_oracle: Pubkey,
+        max_staleness: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
         require!(
-            ctx.accounts.safeguarding_account.key() == state.safeguarding_account,
-            CustomError::UnauthorizedSafeguardingAccount
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
         );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
 
-        let lamports = ctx.accounts.state.to_account_info().lamports();
-        // Calculate the rent-exemption balance for the state account
-        let rent = Rent::get()?;
-        let rent_exempt_balance = rent.minimum_balance(ctx.accounts.state.to_account_info().data_len());
-        
-        // Transfer all lamports except rent-exempt balance and the amount reserved for refunds
-        let transfer_amount = lamports - state.total_cancelled - rent_exempt_balance;
-        **ctx.accounts.state.to_account_info().try_borrow_mut_lamports()? -= transfer_amount;
-        **ctx.accounts.safeguarding_account.to_account_info().try_borrow_mut_lamports()? += transfer_amount;
+        state.eur_cap_cents = eur_cap_cents;
+        state.eur_oracle = eur_oracle;
+        state.oracle_max_staleness = max_staleness;
 
         msg!(
-            "Funds safeguarded; amount: {}, safeguarding account: {}",
-            transfer_amount,
-            ctx.accounts.safeguarding_account.key()
+            "EUR cap configured; cap (EUR cents): {}, oracle: {}, max staleness: {}",
+            eur_cap_cents,
+            eur_oracle,
+            max_staleness
         );
 
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
         Ok(())
     }
 
+    // Safeguard automatically during participation: once the held balance exceeds `threshold`,
+    // everything above `reserve` moves to the safeguarding account. A threshold of 0 disables it.
+    // Automatic movements are recorded in the audit log through `record_auto_safeguard`.
+    // Can be called only by the token provider before activation
+    pub fn configure_auto_safeguard(ctx: Context<Configure>, threshold: u64, reserve: u64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+        require!(reserve <= threshold, CustomError::InvalidSafeguardThreshold);
 
-    // Begin distribution period
-    // Participation period have ended and the duration has passed
-    // Can be called by anyone
-    pub fn distribute(ctx: Context<Distribute>) -> Result<()> {
-        let lamports = ctx.accounts.state.to_account_info().lamports();
-        let now = Clock::get()?.unix_timestamp as u64;
-        // Calculate the rent-exemption balance for the state account
-        let rent = Rent::get()?;
-        let rent_exempt_balance = rent.minimum_balance(ctx.accounts.state.to_account_info().data_len());
+        state.auto_safeguard_threshold = threshold;
+        state.auto_safeguard_reserve = reserve;
 
-        {
-            let state = &mut ctx.accounts.state;
-            let distribution_start_time = state.participation_end + state.duration;  // Use stored duration
-            
-            require!(now >= distribution_start_time, CustomError::WithdrawalWindowStillOpen);
-            
-            // Check if there are sufficient funds for refunds
-            if lamports < state.total_cancelled {
-                let missing_amount = state.total_cancelled.saturating_sub(lamports);
-                msg!("Insufficient balance for refunds. Missing {} lamports", missing_amount);
-                return Err(CustomError::InsufficientRefundBalance.into());
-            }
+        msg!("Automatic safeguarding configured; threshold: {}, reserve: {}", threshold, reserve);
 
-            state.tokens_distributed = true;
-            state.recipient_lamports = lamports - rent_exempt_balance;
-        }
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
-        // Transfer non-cancelled amount to beneficiary
-        let transfer_amount = lamports - rent_exempt_balance;
-        **ctx.accounts.state.to_account_info().try_borrow_mut_lamports()? -= transfer_amount;
-        **ctx.accounts.beneficiary.to_account_info().try_borrow_mut_lamports()? += transfer_amount;
+        Ok(())
+    }
 
-        msg!("Distribution period started; beneficiary: {}, recipient lamports: {}, reserved for refunds: {}", 
-            ctx.accounts.state.beneficiary, 
-            transfer_amount,
-            ctx.accounts.state.total_cancelled
+    // Allow safeguarded SOL to be staked with the validator behind `vote_account`.
+    // Can be called only by the token provider before activation
+    pub fn configure_staking(ctx: Context<Configure>, vote_account: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
         );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+
+        state.stake_vote_account = vote_account;
+
+        msg!("Staking configured; vote account: {}", vote_account);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
         Ok(())
     }
 
-    // Participant claims their tokens
-    // Distribution period has started
-    // Can be called by the participant
-    pub fn claim(ctx: Context<Claim>, _program_token_account_bump: u8) -> Result<()> {
-        let state = &ctx.accounts.state;
-        let participant = ctx.accounts.participant_account.load()?;
-        require!(state.tokens_distributed, CustomError::TokensNotDistributed);
-        require!(participant.participation_time > 0, CustomError::NeverParticipated);
+    // Mint SPL receipt tokens to participants, one per contributed lamport, from a program-controlled mint.
+    // Non-transferable receipts are kept frozen in the participant's token account. Transferable receipts
+    // circulate freely and claims redeem them from the caller, so a position is only settled by whoever
+    // holds its receipts.
+    // Can be called only by the token provider before activation
+    pub fn configure_receipts(ctx: Context<ConfigureReceipts>, transferable: bool) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
 
-        if participant.cancelled == 0 {
-            // Calculate total possible bonus tokens
-            let total_bonus_tokens = 
-                (state.active_early_investor_count as u128 * 1000) + // early investor bonuses
-                (state.large_investor_count as u128  * 1000);         // large investor bonuses
+        state.receipt_mint = ctx.accounts.receipt_mint.key();
+        state.receipt_mint_bump = ctx.bumps.receipt_mint;
+        state.receipts_transferable = transferable;
 
-            // Adjust total tokens by subtracting reserved bonus tokens
-            let distributable_tokens = (state.total_tokens as u128).saturating_sub(total_bonus_tokens);
+        msg!(
+            "Receipts configured; mint: {}, transferable: {}",
+            ctx.accounts.receipt_mint.key(),
+            transferable
+        );
 
-            // Calculate base share from remaining tokens
-            let participant_amount = participant.amount as u128;
-            let total_contributed = state.total_contributed as u128;
-    
-            let mut share = distributable_tokens
-                .checked_mul(participant_amount)
-                .ok_or(CustomError::ArithmeticOverflow)?
-                .checked_div(total_contributed)
-                .ok_or(CustomError::DivisionByZero)? as u64;
-            
-            // Add early investor bonus
-            if participant.is_early_investor == 1 {
-                share += 1000;
-            }
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
 
-            // Add large investor bonus
-            if participant.amount >= 100_000_000_000 {
-                share += 1000;
-            }
-            
-            let token_mint_address = ctx.accounts.token_mint.key();
-            let seeds = &[token_mint_address.as_ref(), &[_program_token_account_bump]];
-            let signer = &[&seeds[..]];
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.program_token_account.to_account_info(),
-                    to: ctx.accounts.participant_token_account.to_account_info(),
-                    authority: ctx.accounts.program_token_account.to_account_info(),
-                },
-                signer,
-            );
-            token::transfer(cpi_ctx, share)?;
-        } else {
+        Ok(())
+    }
 
-            // Participant cancelled - refund SOL
-            let amount = participant.amount;
-            let state_info = ctx.accounts.state.to_account_info();
-            let participant_info = ctx.accounts.participant.to_account_info();
-            
-            // Get current balances
-            let state_lamports = state_info.lamports();
-            
-            // Ensure state has enough lamports for the refund
-            require!(
-                state_lamports >= amount,
-                CustomError::InsufficientRefundBalance
-            );
-            
-            // Transfer lamports using safe arithmetic operations
-            let new_state_lamports = state_lamports.checked_sub(amount)
-                .ok_or(CustomError::ArithmeticOverflow)?;
-            let new_participant_lamports = participant_info.lamports()
-                .checked_add(amount)
-                .ok_or(CustomError::ArithmeticOverflow)?;
-            
-            // Update lamport balances
-            **state_info.try_borrow_mut_lamports()? = new_state_lamports;
-            **participant_info.try_borrow_mut_lamports()? = new_participant_lamports;
-        }
+    pub fn activate(ctx: Context<Activate>, duration: u64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.participation_active, CustomError::AlreadyActivated);
+        require!(state.white_paper_version > 0, CustomError::WhitePaperMissing);
+
+        state.participation_active = true;
+        state.duration = duration;  // Save the duration
+        state.participation_start = Clock::get()?.unix_timestamp as u64;
+        state.participation_end = state.participation_start + duration;
+
+        msg!(
+            "Participation period activated; participation period ends at: {}",
+            state.participation_end
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Activate,
+            ctx.accounts.token_provider.key(),
+            duration,
+        )?;
 
         Ok(())
     }
 
-    // Helper function to decrease time for participation_end for tests
-    pub fn end(ctx: Context<End>) -> Result<()> {
+    // Set the authority that maintains the denylist of sanctioned addresses.
+    // Can be called only by the token provider
+    pub fn configure_denylist_authority(ctx: Context<Configure>, denylist_authority: Pubkey) -> Result<()> {
         let state = &mut ctx.accounts.state;
         require!(
             ctx.accounts.token_provider.key() == state.token_provider,
             CustomError::UnauthorizedCaller
         );
-        
-        state.participation_end = Clock::get()?.unix_timestamp as u64;
-        state.duration = 0;
+
+        state.denylist_authority = denylist_authority;
+
+        msg!("Denylist authority configured; denylist authority: {}", denylist_authority);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Configure,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
         Ok(())
     }
 
-}
+    // Block a wallet from participating and claiming. Until distribution, its active contribution
+    // is reserved on the contract for a refund instead of being paid out.
+    // Can be called only by the denylist authority
+    pub fn add_to_denylist(ctx: Context<AddToDenylist>, wallet: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.denylist_authority.key() == state.denylist_authority,
+            CustomError::UnauthorizedCaller
+        );
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
-    #[account(
-        init,
-        payer = signer,
-        space = 8 + State::INIT_SPACE,
-        seeds = [b"state"],
-        bump
-    )]
-    pub state: Account<'info, State>,
-    pub system_program: Program<'info, System>,
-}
+        // Until distribution, the contribution is reserved for its refund and its bonuses are
+        // no longer set aside; shares are final once tokens have been distributed
+        let (reserved, early_investor, large_investor) = match read_participant(&ctx.accounts.participant_account)? {
+            Some(participant) if !state.tokens_distributed => (
+                participant.amount,
+                participant.is_early_investor == 1,
+                participant.amount >= 100_000_000_000,
+            ),
+            _ => (0, false, false),
+        };
+        state.total_denylisted += reserved;
+        if early_investor {
+            state.active_early_investor_count -= 1;
+        }
+        if large_investor {
+            state.large_investor_count -= 1;
+        }
 
-#[account]
-#[derive(InitSpace)]
-pub struct State {
-    pub initialized: bool,
-    pub participation_active: bool,
-    pub participation_end: u64,
-    pub raise_cap: u64,
-    pub total_contributed: u64,
-    pub total_tokens: u64,
-    pub tokens_distributed: bool,
-    pub recipient_lamports: u64,
-    pub token_provider: Pubkey,
-    pub beneficiary: Pubkey,
+        let entry = &mut ctx.accounts.denylist_entry;
+        entry.wallet = wallet;
+        entry.listed_at = Clock::get()?.unix_timestamp as u64;
+        entry.refund_allowed = false;
+        entry.reserved_lamports = reserved;
+        entry.early_investor = early_investor;
+        entry.large_investor = large_investor;
+
+        msg!("Wallet denylisted; wallet: {}, reserved lamports: {}", wallet, reserved);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::AddToDenylist,
+            ctx.accounts.denylist_authority.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Unblock a wallet, e.g. after it was removed from a sanctions list.
+    // A contribution kept out of the payout at distribution can only be refunded.
+    // Can be called only by the denylist authority
+    pub fn remove_from_denylist(ctx: Context<RemoveFromDenylist>, wallet: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.denylist_authority.key() == state.denylist_authority,
+            CustomError::UnauthorizedCaller
+        );
+
+        let entry = &ctx.accounts.denylist_entry;
+        let reserved = entry.reserved_lamports;
+        require!(reserved == 0 || !state.tokens_distributed, CustomError::DistributionAlreadyStarted);
+        state.total_denylisted -= reserved;
+        // Set the bonuses aside again
+        if entry.early_investor {
+            state.active_early_investor_count += 1;
+        }
+        if entry.large_investor {
+            state.large_investor_count += 1;
+        }
+
+        msg!("Wallet removed from denylist; wallet: {}", wallet);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::RemoveFromDenylist,
+            ctx.accounts.denylist_authority.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Allow a denylisted wallet to claim a refund of its contribution (never tokens),
+    // once a court or regulator has authorised the release.
+    // Can be called only by the denylist authority
+    pub fn allow_denylisted_refund(ctx: Context<UpdateDenylist>, wallet: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.denylist_authority.key() == ctx.accounts.state.denylist_authority,
+            CustomError::UnauthorizedCaller
+        );
+
+        ctx.accounts.denylist_entry.refund_allowed = true;
+
+        msg!("Refund allowed for denylisted wallet; wallet: {}", wallet);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::AllowDenylistedRefund,
+            ctx.accounts.denylist_authority.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Initialize a participant's participation account.
+    // Must be called by the participant before participating.
+    pub fn init_participant(ctx: Context<InitParticipant>) -> Result<()> {
+        require_not_denylisted(&ctx.accounts.denylist_entry)?;
+
+        let mut participant = ctx.accounts.participant_account.load_init()?;
+        participant.participant = ctx.accounts.participant.key();
+        participant.amount = 0;
+        participant.participation_time = 0;
+        participant.cancelled = 0;
+        participant.rent_payer = ctx.accounts.payer.key();
+        
+        Ok(())
+    }
+
+    // During the priority window, `proof` must show that the leaf (participant, max_allocation)
+    // is part of the sale's Merkle root. A max_allocation of 0 means no per-leaf limit.
+    // `terms_hash` attests the white paper version the participant accepted and must be the current one.
+    pub fn participate(
+        ctx: Context<Participate>,
+        amount: u64,
+        max_allocation: u64,
+        proof: Vec<[u8; 32]>,
+        terms_hash: [u8; 32],
+    ) -> Result<()> {
+        record_participation(
+            &mut ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.participant.to_account_info(),
+            &ctx.accounts.participant.key(),
+            &ctx.accounts.payer.key(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.price_oracle,
+            amount,
+            max_allocation,
+            &proof,
+            terms_hash,
+            true, // the participant accepts the terms
+        )?;
+
+        // Mint receipts and let the receipt mint burn them on cancel or claim
+        if let Some((receipt_mint, receipt_token_account)) = receipt_accounts(
+            &ctx.accounts.state,
+            &ctx.accounts.receipt_mint,
+            &ctx.accounts.receipt_token_account,
+            &ctx.accounts.participant.key(),
+        )? {
+            let state = &ctx.accounts.state;
+            let token_program = ctx.accounts.token_program.as_ref().ok_or(CustomError::InvalidReceiptAccount)?;
+            let seeds: &[&[u8]] = &[b"receipt_mint", &[state.receipt_mint_bump]];
+            thaw_receipts(state, receipt_mint, receipt_token_account, token_program)?;
+            token::mint_to(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    token::MintTo {
+                        mint: receipt_mint.to_account_info(),
+                        to: receipt_token_account.to_account_info(),
+                        authority: receipt_mint.to_account_info(),
+                    },
+                    &[seeds],
+                ),
+                amount,
+            )?;
+            if !state.receipts_transferable {
+                token::approve(
+                    CpiContext::new(
+                        token_program.to_account_info(),
+                        token::Approve {
+                            to: receipt_token_account.to_account_info(),
+                            delegate: receipt_mint.to_account_info(),
+                            authority: ctx.accounts.participant.to_account_info(),
+                        },
+                    ),
+                    receipt_token_account.amount + amount,
+                )?;
+                token::freeze_account(CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    token::FreezeAccount {
+                        account: receipt_token_account.to_account_info(),
+                        mint: receipt_mint.to_account_info(),
+                        authority: receipt_mint.to_account_info(),
+                    },
+                    &[seeds],
+                ))?;
+            }
+        }
+
+        auto_safeguard(&mut ctx.accounts.state, &ctx.accounts.safeguarding_account)?;
+
+        Ok(())
+    }
+
+    // Participate on behalf of `participant`, who owns the position while the funder pays for it.
+    // Caps and the priority allocation apply to the owner; the funder accepts the terms, which
+    // is not recorded as the owner's acceptance.
+    // Not available while receipt tokens are enabled, as receipts need the owner's approval
+    pub fn participate_for(
+        ctx: Context<ParticipateFor>,
+        amount: u64,
+        max_allocation: u64,
+        proof: Vec<[u8; 32]>,
+        terms_hash: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.state.receipt_mint == Pubkey::default(),
+            CustomError::ReceiptsRequireOwner
+        );
+        require_not_denylisted(&ctx.accounts.funder_denylist_entry)?;
+
+        record_participation(
+            &mut ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.funder.to_account_info(),
+            &ctx.accounts.participant.key(),
+            &ctx.accounts.funder.key(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.price_oracle,
+            amount,
+            max_allocation,
+            &proof,
+            terms_hash,
+            false, // the funder's acceptance is not the owner's
+        )?;
+
+        msg!(
+            "Participation funded; owner: {}, funder: {}, amount: {}",
+            ctx.accounts.participant.key(),
+            ctx.accounts.funder.key(),
+            amount
+        );
+
+        auto_safeguard(&mut ctx.accounts.state, &ctx.accounts.safeguarding_account)?;
+
+        Ok(())
+    }
+
+    // Participate with an intent the participant signed off-chain. The intent is verified through an
+    // ed25519 program instruction placed right before this one; its message is built by `intent_message`.
+    // The relayer submits the transaction and pays the fees, the account rent and the contribution.
+    // Intent nonces must be used in order, starting at 0, so each intent can be executed only once.
+    // They are tracked per participant wallet, apart from the position, so transfers do not reset them
+    #[allow(clippy::too_many_arguments)]
+    pub fn participate_with_intent(
+        ctx: Context<ParticipateWithIntent>,
+        amount: u64,
+        max_allocation: u64,
+        proof: Vec<[u8; 32]>,
+        terms_hash: [u8; 32],
+        nonce: u64,
+        expiry: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        require!(now <= expiry, CustomError::IntentExpired);
+        require!(
+            ctx.accounts.state.receipt_mint == Pubkey::default(),
+            CustomError::ReceiptsRequireOwner
+        );
+        require_not_denylisted(&ctx.accounts.relayer_denylist_entry)?;
+
+        let message = intent_message(
+            &ctx.accounts.state.key(),
+            &ctx.accounts.participant.key(),
+            amount,
+            max_allocation,
+            &terms_hash,
+            nonce,
+            expiry,
+        );
+        verify_ed25519_intent(&ctx.accounts.instructions, &ctx.accounts.participant.key(), &message)?;
+
+        let intent_nonce = &mut ctx.accounts.intent_nonce;
+        require!(nonce == intent_nonce.next, CustomError::InvalidIntentNonce);
+        intent_nonce.next += 1;
+
+        record_participation(
+            &mut ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.relayer.to_account_info(),
+            &ctx.accounts.participant.key(),
+            &ctx.accounts.relayer.key(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.price_oracle,
+            amount,
+            max_allocation,
+            &proof,
+            terms_hash,
+            true, // the participant accepted the terms by signing the intent
+        )?;
+
+        msg!(
+            "Intent executed; participant: {}, relayer: {}, amount: {}, nonce: {}",
+            ctx.accounts.participant.key(),
+            ctx.accounts.relayer.key(),
+            amount,
+            nonce
+        );
+
+        auto_safeguard(&mut ctx.accounts.state, &ctx.accounts.safeguarding_account)?;
+
+        Ok(())
+    }
+
+    // Cancel participation and signal intent to cancel (must be done within 14 days of participation).
+    // Can be called by the participant
+    pub fn cancel(ctx: Context<Cancel>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let mut participant = ctx.accounts.participant_account.load_mut()?;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        // A denylisted contribution is already reserved for its refund
+        require_not_denylisted(&ctx.accounts.denylist_entry)?;
+        require!(now <= state.participation_end, CustomError::WithdrawalClosed);
+        require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);
+        
+        let amount = participant.amount;
+        require!(amount > 0, CustomError::NoContribution);
+
+        // Decrease large investor count if applicable
+        if amount >= 100_000_000_000 {
+            state.large_investor_count = state.large_investor_count.saturating_sub(1);
+        }
+
+        // Clear early investor flag and decrement active count
+        if participant.is_early_investor == 1 {
+            participant.is_early_investor = 0;
+            state.active_early_investor_count = state.active_early_investor_count.saturating_sub(1);
+        }
+
+        // The active amount becomes refundable; the participant may contribute again later
+        state.total_cancelled += amount;
+        participant.cancelled = 1;
+        participant.cancelled_amount += amount;
+        participant.amount = 0;
+        state.total_contributed -= amount;
+
+        if let Some((receipt_mint, receipt_token_account)) = receipt_accounts(
+            &ctx.accounts.state,
+            &ctx.accounts.receipt_mint,
+            &ctx.accounts.receipt_token_account,
+            &ctx.accounts.participant.key(),
+        )? {
+            let token_program = ctx.accounts.token_program.as_ref().ok_or(CustomError::InvalidReceiptAccount)?;
+            burn_receipts(
+                &ctx.accounts.state,
+                receipt_mint,
+                receipt_token_account,
+                &ctx.accounts.participant.to_account_info(),
+                token_program,
+                amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Move the participant's position to `new_owner`, who must not have a participant account yet.
+    // Claim options and the accepted terms are reset, and the old account rent goes back to whoever paid it.
+    // Can be called by the participant before claiming, unless their cancellation refund is pending,
+    // once the priority round has ended
+    pub fn transfer_position(ctx: Context<TransferPosition>, new_owner: Pubkey) -> Result<()> {
+        require_not_denylisted(&ctx.accounts.denylist_entry)?;
+        require_not_denylisted(&ctx.accounts.new_owner_denylist_entry)?;
+        // Receipts stay with the old owner, so positions backed by receipts cannot move
+        require!(
+            ctx.accounts.state.receipt_mint == Pubkey::default(),
+            CustomError::PositionLocked
+        );
+        // Priority allocations are per wallet, so positions cannot move them to another wallet
+        require!(
+            ctx.accounts.state.merkle_root == [0u8; 32]
+                || Clock::get()?.unix_timestamp as u64 >= ctx.accounts.state.priority_end,
+            CustomError::PositionLocked
+        );
+        // Votes are cast per wallet, so positions are frozen once tranche votes can open
+        require!(
+            !ctx.accounts.state.tranches.iter().any(|tranche| tranche.approved),
+            CustomError::PositionLocked
+        );
+
+        let old = ctx.accounts.participant_account.load()?;
+        require!(old.claimed == 0, CustomError::AlreadyClaimed);
+        require!(old.cancelled == 0, CustomError::ParticipationCancelledAlready);
+        let amount = old.amount;
+        require!(amount > 0, CustomError::NoContribution);
+
+        let mut new = ctx.accounts.new_participant_account.load_init()?;
+        new.participant = new_owner;
+        new.amount = amount;
+        new.participation_time = old.participation_time;
+        new.is_early_investor = old.is_early_investor;
+        // The new owner has not accepted the terms yet
+        new.accepted_terms_version = 0;
+        new.rent_payer = ctx.accounts.payer.key();
+
+        msg!(
+            "Position transferred; from: {}, to: {}, amount: {}",
+            ctx.accounts.participant.key(),
+            new_owner,
+            amount
+        );
+
+        drop(old);
+        drop(new);
+        ctx.accounts.participant_account.close(ctx.accounts.rent_payer.to_account_info())?;
+
+        Ok(())
+    }
+
+    // Moves `amount` SOL to the safeguarding account.
+    // Can be called only by the token provider
+    pub fn safeguard(ctx: Context<Safeguard>, amount: u64) -> Result<()> {
+        let state = &ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);   // Safeguarding can only be called before the distribution period starts
+        require!(
+            ctx.accounts.safeguarding_account.key() == state.safeguarding_account,
+            CustomError::UnauthorizedSafeguardingAccount
+        );
+
+        let lamports = ctx.accounts.state.to_account_info().lamports();
+        // Calculate the rent-exemption balance for the state account
+        let rent = Rent::get()?;
+        let rent_exempt_balance = rent.minimum_balance(ctx.accounts.state.to_account_info().data_len());
+        
+        // Keep the rent-exempt balance and the amounts reserved for refunds
+        let available = lamports.saturating_sub(refund_reserve(state) + rent_exempt_balance);
+        require!(amount <= available, CustomError::SafeguardAmountExceeded);
+
+        **ctx.accounts.state.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.safeguarding_account.to_account_info().try_borrow_mut_lamports()? += amount;
+        ctx.accounts.state.total_safeguarded += amount;
+
+        msg!(
+            "Funds safeguarded; amount: {}, total safeguarded: {}, safeguarding account: {}",
+            amount,
+            ctx.accounts.state.total_safeguarded,
+            ctx.accounts.safeguarding_account.key()
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Safeguard,
+            ctx.accounts.token_provider.key(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+
+    // Moves safeguarded SOL back to the contract so late cancellations and refunds can be honored.
+    // Can be called only by the safeguarding account before the distribution period starts, as
+    // lamports returned after distribution could no longer be paid out
+    pub fn return_from_safeguard(ctx: Context<ReturnFromSafeguard>, amount: u64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.safeguarding_account.key() == state.safeguarding_account,
+            CustomError::UnauthorizedSafeguardingAccount
+        );
+        require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);
+        require!(
+            state.total_returned_from_safeguard + amount <= state.total_safeguarded,
+            CustomError::SafeguardAmountExceeded
+        );
+
+        state.total_returned_from_safeguard += amount;
+
+        let transfer_instruction = system_instruction::transfer(
+            &ctx.accounts.safeguarding_account.key(),
+            &ctx.accounts.state.key(),
+            amount,
+        );
+        invoke(
+            &transfer_instruction,
+            &[
+                ctx.accounts.safeguarding_account.to_account_info(),
+                ctx.accounts.state.to_account_info(),
+            ],
+        )?;
+
+        msg!(
+            "Funds returned from safeguarding; amount: {}, total returned: {}",
+            amount,
+            ctx.accounts.state.total_returned_from_safeguard
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::ReturnFromSafeguard,
+            ctx.accounts.safeguarding_account.key(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    // Record the funds safeguarded automatically since the last record as one audit log entry, so
+    // participations never create audit entries. All of them must be recorded before distribution.
+    // Can be called only by the token provider
+    pub fn record_auto_safeguard(ctx: Context<Configure>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        let amount = state.unrecorded_auto_safeguarded;
+        require!(amount > 0, CustomError::NoAutoSafeguardToRecord);
+
+        state.unrecorded_auto_safeguarded = 0;
+
+        msg!("Automatic safeguarding recorded; amount: {}", amount);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::AutoSafeguard,
+            ctx.accounts.token_provider.key(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    // Safeguard `amount` SOL by delegating it from the contract to a program-controlled
+    // native stake account with the configured validator. Can be done once per sale.
+    // Can be called only by the token provider before the distribution period starts
+    pub fn stake_safeguarded(ctx: Context<StakeSafeguarded>, amount: u64) -> Result<()> {
+        let state = &ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.tokens_distributed, CustomError::DistributionAlreadyStarted);
+        require!(state.stake_vote_account != Pubkey::default(), CustomError::StakingNotConfigured);
+        require!(
+            ctx.accounts.vote_account.key() == state.stake_vote_account,
+            CustomError::InvalidVoteAccount
+        );
+        require!(!state.stake_active && state.staked_lamports == 0, CustomError::StakeAlreadyCreated);
+
+        // Keep the rent-exempt balance and the amounts reserved for refunds
+        let state_info = ctx.accounts.state.to_account_info();
+        let rent = Rent::get()?;
+        let rent_exempt_balance = rent.minimum_balance(state_info.data_len());
+        let available = state_info.lamports().saturating_sub(refund_reserve(state) + rent_exempt_balance);
+        require!(amount <= available, CustomError::SafeguardAmountExceeded);
+
+        // Create the stake account; its rent is paid by the token provider and returned on withdrawal
+        let stake_rent = rent.minimum_balance(StakeStateV2::size_of());
+        let stake_bump = ctx.bumps.stake_account;
+        let stake_seeds: &[&[u8]] = &[b"stake", &[stake_bump]];
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: ctx.accounts.token_provider.to_account_info(),
+                    to: ctx.accounts.stake_account.to_account_info(),
+                },
+                &[stake_seeds],
+            ),
+            stake_rent,
+            StakeStateV2::size_of() as u64,
+            &stake::program::ID,
+        )?;
+        transfer_lamports(&state_info, &ctx.accounts.stake_account, amount)?;
+
+        // The state PDA is both staker and withdrawer
+        invoke(
+            &stake::instruction::initialize(
+                &ctx.accounts.stake_account.key(),
+                &Authorized::auto(&ctx.accounts.state.key()),
+                &Lockup::default(),
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.rent.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+        )?;
+        let state_seeds: &[&[u8]] = &[b"state", &[ctx.bumps.state]];
+        invoke_signed(
+            &stake::instruction::delegate_stake(
+                &ctx.accounts.stake_account.key(),
+                &ctx.accounts.state.key(),
+                &ctx.accounts.vote_account.key(),
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.vote_account.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.stake_history.to_account_info(),
+                ctx.accounts.stake_config.to_account_info(),
+                ctx.accounts.state.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            &[state_seeds],
+        )?;
+
+        let state = &mut ctx.accounts.state;
+        state.stake_active = true;
+        state.staked_lamports = amount;
+        state.stake_rent_lamports = stake_rent;
+        state.total_safeguarded += amount;
+
+        msg!(
+            "Funds staked; amount: {}, stake account: {}, vote account: {}",
+            amount,
+            ctx.accounts.stake_account.key(),
+            ctx.accounts.vote_account.key()
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::StakeSafeguarded,
+            ctx.accounts.token_provider.key(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    // Deactivate the stake so it can be withdrawn back before distribution.
+    // Can be called only by the token provider
+    pub fn deactivate_stake(ctx: Context<DeactivateStake>) -> Result<()> {
+        require!(
+            ctx.accounts.token_provider.key() == ctx.accounts.state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(ctx.accounts.state.stake_active, CustomError::StakeNotActive);
+
+        let state_seeds: &[&[u8]] = &[b"state", &[ctx.bumps.state]];
+        invoke_signed(
+            &stake::instruction::deactivate_stake(
+                &ctx.accounts.stake_account.key(),
+                &ctx.accounts.state.key(),
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.state.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            &[state_seeds],
+        )?;
+
+        msg!("Stake deactivated; stake account: {}", ctx.accounts.stake_account.key());
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::DeactivateStake,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Withdraw the deactivated stake back to the contract. Rewards stay on the contract for the
+    // beneficiary and the stake account rent goes back to the token provider.
+    // Can be called only by the token provider once the stake has cooled down
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> Result<()> {
+        require!(
+            ctx.accounts.token_provider.key() == ctx.accounts.state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(ctx.accounts.state.stake_active, CustomError::StakeNotActive);
+
+        let withdrawn = ctx.accounts.stake_account.lamports();
+        let state_seeds: &[&[u8]] = &[b"state", &[ctx.bumps.state]];
+        invoke_signed(
+            &stake::instruction::withdraw(
+                &ctx.accounts.stake_account.key(),
+                &ctx.accounts.state.key(),
+                &ctx.accounts.state.key(),
+                withdrawn,
+                None,
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.state.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.stake_history.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            &[state_seeds],
+        )?;
+
+        let stake_rent = ctx.accounts.state.stake_rent_lamports;
+        transfer_lamports(
+            &ctx.accounts.state.to_account_info(),
+            &ctx.accounts.token_provider.to_account_info(),
+            stake_rent,
+        )?;
+
+        let state = &mut ctx.accounts.state;
+        state.stake_active = false;
+        state.staking_rewards = withdrawn.saturating_sub(state.staked_lamports + stake_rent);
+        state.total_returned_from_safeguard += state.staked_lamports;
+
+        msg!(
+            "Stake withdrawn; amount: {}, staking rewards: {}",
+            withdrawn,
+            state.staking_rewards
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::WithdrawStake,
+            ctx.accounts.token_provider.key(),
+            withdrawn - stake_rent,
+        )?;
+
+        Ok(())
+    }
+
+    // Begin distribution period
+    // Participation period have ended and the duration has passed
+    // Can be called by anyone
+    pub fn distribute(ctx: Context<Distribute>) -> Result<()> {
+        let lamports = ctx.accounts.state.to_account_info().lamports();
+        let now = Clock::get()?.unix_timestamp as u64;
+        require!(
+            ctx.accounts.beneficiary.key() == ctx.accounts.state.beneficiary,
+            CustomError::UnauthorizedBeneficiary
+        );
+        require!(
+            ctx.accounts.fee_recipient.key() == ctx.accounts.state.fee_recipient,
+            CustomError::UnauthorizedFeeRecipient
+        );
+        // Calculate the rent-exemption balance for the state account
+        let rent = Rent::get()?;
+        let rent_exempt_balance = rent.minimum_balance(ctx.accounts.state.to_account_info().data_len());
+
+        {
+            let state = &mut ctx.accounts.state;
+            let distribution_start_time = state.participation_end + state.duration;  // Use stored duration
+            
+            require!(now >= distribution_start_time, CustomError::WithdrawalWindowStillOpen);
+            require!(!state.stake_active, CustomError::StakeStillActive);
+            require!(state.unrecorded_auto_safeguarded == 0, CustomError::AutoSafeguardNotRecorded);
+            
+            // Keep the contributions above the raise cap on the contract for pro-rata refunds
+            let allocated = allocated_contributions(state);
+            if state.oversubscription_enabled && allocated > state.raise_cap {
+                state.total_oversubscribed = allocated - state.raise_cap;
+            }
+
+            // Cancelled, oversubscribed and denylisted contributions stay on the contract for their refunds
+            let reserved = state.total_cancelled + state.total_oversubscribed + state.total_denylisted;
+            if lamports < rent_exempt_balance + reserved {
+                let missing_amount = (rent_exempt_balance + reserved).saturating_sub(lamports);
+                msg!("Insufficient balance for refunds. Missing {} lamports", missing_amount);
+                return Err(CustomError::InsufficientRefundBalance.into());
+            }
+
+            // Split the payout between the beneficiary and the platform fee recipient
+            let payout = lamports - rent_exempt_balance - reserved;
+            let fee_lamports = (payout as u128)
+                .checked_mul(state.fee_bps as u128)
+                .ok_or(CustomError::ArithmeticOverflow)?
+                / 10_000;
+
+            state.tokens_distributed = true;
+            state.fee_lamports = fee_lamports as u64;
+            state.recipient_lamports = payout - state.fee_lamports;
+        }
+
+        // Transfer the fee, then the non-cancelled amount to the beneficiaries unless it is escrowed
+        let transfer_amount = ctx.accounts.state.recipient_lamports;
+        let fee_amount = ctx.accounts.state.fee_lamports;
+        **ctx.accounts.state.to_account_info().try_borrow_mut_lamports()? -= fee_amount;
+        **ctx.accounts.fee_recipient.to_account_info().try_borrow_mut_lamports()? += fee_amount;
+
+        if ctx.accounts.state.tranches.is_empty() {
+            pay_beneficiaries(
+                &mut ctx.accounts.state,
+                &ctx.accounts.beneficiary,
+                ctx.remaining_accounts,
+                transfer_amount,
+            )?;
+        } else {
+            ctx.accounts.state.escrowed_lamports = transfer_amount;
+            msg!("Funds escrowed; escrowed lamports: {}", transfer_amount);
+        }
+
+        msg!("Distribution period started; beneficiary: {}, recipient lamports: {}, fee lamports: {}, reserved for refunds: {}, oversubscribed: {}, denylisted: {}", 
+            ctx.accounts.state.beneficiary, 
+            transfer_amount,
+            fee_amount,
+            ctx.accounts.state.total_cancelled,
+            ctx.accounts.state.total_oversubscribed,
+            ctx.accounts.state.total_denylisted
+        );
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::Distribute,
+            ctx.accounts.payer.key(),
+            transfer_amount,
+        )?;
+
+        Ok(())
+    }
+
+    // Approve the release of an escrowed tranche.
+    // Can be called only by the escrow custodian
+    pub fn approve_tranche(ctx: Context<ApproveTranche>, index: u8) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.custodian.key() == state.escrow_custodian,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.escrow_refunding, CustomError::EscrowRefunding);
+        let vote_duration = state.tranche_vote_duration;
+        let tranche = state.tranches.get_mut(index as usize).ok_or(CustomError::InvalidTranche)?;
+        require!(!tranche.released, CustomError::TrancheAlreadyReleased);
+        // A second approval would reopen the vote
+        require!(!tranche.approved, CustomError::TrancheAlreadyApproved);
+
+        tranche.approved = true;
+
+        // Approval opens the participant vote on this tranche
+        if vote_duration > 0 {
+            tranche.vote_end = Clock::get()?.unix_timestamp as u64 + vote_duration;
+        }
+
+        msg!("Tranche approved; tranche: {}, vote ends at: {}", index, tranche.vote_end);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::ApproveTranche,
+            ctx.accounts.custodian.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // Release an approved tranche to the beneficiaries once its unlock time has passed.
+    // Can be called by anyone
+    pub fn release_tranche(ctx: Context<ReleaseTranche>, index: u8) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        require!(
+            ctx.accounts.beneficiary.key() == ctx.accounts.state.beneficiary,
+            CustomError::UnauthorizedBeneficiary
+        );
+
+        let amount = {
+            let state = &mut ctx.accounts.state;
+            require!(state.tokens_distributed, CustomError::TokensNotDistributed);
+            require!(!state.escrow_refunding, CustomError::EscrowRefunding);
+            let escrowed = state.escrowed_lamports as u128;
+            let voting = state.tranche_vote_duration > 0;
+
+            // Cumulative rounding keeps the sum of all tranches equal to the escrowed amount
+            let bps_before: u128 = state.tranches.iter().take(index as usize).map(|t| t.bps as u128).sum();
+            let tranche = state.tranches.get_mut(index as usize).ok_or(CustomError::InvalidTranche)?;
+            require!(tranche.approved, CustomError::TrancheNotApproved);
+            require!(!tranche.released, CustomError::TrancheAlreadyReleased);
+            require!(now >= tranche.unlock_time, CustomError::TrancheLocked);
+            if voting {
+                require!(now > tranche.vote_end, CustomError::VoteStillOpen);
+                require!(tranche.votes_for > tranche.votes_against, CustomError::VoteFailed);
+            }
+
+            let amount = (escrowed * (bps_before + tranche.bps as u128) / 10_000
+                - escrowed * bps_before / 10_000) as u64;
+            tranche.released = true;
+            tranche.released_lamports = amount;
+            state.released_lamports += amount;
+            amount
+        };
+
+        pay_beneficiaries(
+            &mut ctx.accounts.state,
+            &ctx.accounts.beneficiary,
+            ctx.remaining_accounts,
+            amount,
+        )?;
+
+        msg!("Tranche released; tranche: {}, lamports: {}", index, amount);
+
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::ReleaseTranche,
+            ctx.accounts.payer.key(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    // Set the wallet receiving the participant's tokens and the delegate allowed to claim for them.
+    // Pass the default pubkey to unset either.
+    // Can be called by the participant until they have claimed
+    pub fn set_claim_options(ctx: Context<SetClaimOptions>, destination: Pubkey, delegate: Pubkey) -> Result<()> {
+        let mut participant = ctx.accounts.participant_account.load_mut()?;
+        require!(participant.claimed == 0, CustomError::AlreadyClaimed);
+
+        participant.claim_destination = destination;
+        participant.claim_delegate = delegate;
+
+        msg!(
+            "Claim options set; participant: {}, destination: {}, delegate: {}",
+            ctx.accounts.participant.key(),
+            destination,
+            delegate
+        );
+
+        Ok(())
+    }
+
+    // Participant claims their tokens
+    // Distribution period has started
+    // Can be called by the participant
+    pub fn claim(ctx: Context<Claim>, _program_token_account_bump: u8) -> Result<()> {
+        let participant = ctx.accounts.participant.to_account_info();
+        settle_claim(
+            &ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.participant.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.participant_token_account.to_account_info(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.token_program,
+            _program_token_account_bump,
+            receipt_accounts(
+                &ctx.accounts.state,
+                &ctx.accounts.receipt_mint,
+                &ctx.accounts.receipt_token_account,
+                participant.key,
+            )?,
+            &participant, // the caller holds the receipts in either mode
+        )
+    }
+
+    // Settle a participant through their claim delegate. Tokens go to the claim destination if one is set
+    // Distribution period has started
+    // Can be called by the participant's claim delegate
+    pub fn delegated_claim(ctx: Context<DelegatedClaim>) -> Result<()> {
+        let (participant, delegate) = (ctx.accounts.participant.to_account_info(), ctx.accounts.delegate.to_account_info());
+        settle_claim(
+            &ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.participant.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.participant_token_account.to_account_info(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.token_program,
+            ctx.bumps.program_token_account,
+            receipt_accounts(
+                &ctx.accounts.state,
+                &ctx.accounts.receipt_mint,
+                &ctx.accounts.receipt_token_account,
+                receipt_holder(&ctx.accounts.state, &participant, &delegate).key,
+            )?,
+            receipt_holder(&ctx.accounts.state, &participant, &delegate),
+        )?;
+
+        msg!(
+            "Delegated claim; participant: {}, delegate: {}",
+            ctx.accounts.participant.key(),
+            ctx.accounts.delegate.key()
+        );
+
+        Ok(())
+    }
+
+    // Settle a participant on their behalf, creating their associated token account if it is missing.
+    // Distribution period has started
+    // Can be called by anyone; the caller pays for the token account
+    pub fn push_claim(ctx: Context<PushClaim>) -> Result<()> {
+        let (participant, payer) = (ctx.accounts.participant.to_account_info(), ctx.accounts.payer.to_account_info());
+        settle_claim(
+            &ctx.accounts.state,
+            &ctx.accounts.participant_account,
+            &ctx.accounts.participant.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.denylist_entry,
+            &ctx.accounts.program_token_account,
+            &ctx.accounts.participant_token_account.to_account_info(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.token_program,
+            ctx.bumps.program_token_account,
+            receipt_accounts(
+                &ctx.accounts.state,
+                &ctx.accounts.receipt_mint,
+                &ctx.accounts.receipt_token_account,
+                receipt_holder(&ctx.accounts.state, &participant, &payer).key,
+            )?,
+            receipt_holder(&ctx.accounts.state, &participant, &payer),
+        )?;
+
+        msg!(
+            "Claim pushed; participant: {}, caller: {}",
+            ctx.accounts.participant.key(),
+            ctx.accounts.payer.key()
+        );
+
+        Ok(())
+    }
+
+    // Settle many participants at once. `remaining_accounts` holds one group per participant: their
+    // participant account, their wallet, the rent payer of their participant account, their token account
+    // for the mint and their denylist PDA, followed by their receipt token account when receipts are enabled.
+    // Entries are settled as by `claim`, including oversubscription refunds, and their participant accounts
+    // are closed; while tranche votes are configured they stay open until `settle_escrow` closes them.
+    // Settled, cancelled and denylisted entries are skipped and left to `claim` or `push_claim`.
+    // Not available with transferable receipts, which the caller of each claim must redeem.
+    // Distribution period has started
+    // Can be called by anyone
+    pub fn batch_claim<'info>(ctx: Context<'_, '_, 'info, 'info, BatchClaim<'info>>) -> Result<()> {
+        let state = &ctx.accounts.state;
+        require!(state.tokens_distributed, CustomError::TokensNotDistributed);
+        require!(!state.receipts_transferable, CustomError::ReceiptsRedeemedByCaller);
+        let receipts_enabled = state.receipt_mint != Pubkey::default();
+        let group_len = if receipts_enabled { 6 } else { 5 };
+        require!(ctx.remaining_accounts.chunks_exact(group_len).remainder().is_empty(), CustomError::InvalidBatch);
+
+        let token_mint = ctx.accounts.token_mint.key();
+        let mut paid = 0u32;
+
+        for group in ctx.remaining_accounts.chunks_exact(group_len) {
+            let (participant_info, wallet_info, rent_payer_info, token_account_info, denylist_info) =
+                (&group[0], &group[1], &group[2], &group[3], &group[4]);
+
+            // Closed accounts have already been settled
+            if participant_info.owner != ctx.program_id || participant_info.data_is_empty() {
+                continue;
+            }
+            let participant_account = AccountLoader::<ParticipantAccount>::try_from(participant_info)?;
+            {
+                let participant = participant_account.load()?;
+                require!(
+                    wallet_info.key() == participant.participant && rent_payer_info.key() == participant.rent_payer,
+                    CustomError::InvalidBatch
+                );
+                let token_account = Account::<TokenAccount>::try_from(token_account_info)?;
+                require!(
+                    token_account.mint == token_mint && token_account.owner == participant.token_owner(),
+                    CustomError::InvalidBatch
+                );
+                let (denylist_pda, _) = Pubkey::find_program_address(
+                    &[b"denylist", participant.participant.as_ref()],
+                    ctx.program_id,
+                );
+                require!(denylist_info.key() == denylist_pda, CustomError::InvalidBatch);
+
+                if participant.claimed == 1
+                    || participant.participation_time == 0
+                    || participant.amount == 0
+                    || participant.cancelled_amount > 0
+                    || !denylist_info.data_is_empty()
+                {
+                    continue;
+                }
+            }
+
+            let receipt_token_account = if receipts_enabled {
+                Some(Account::<TokenAccount>::try_from(&group[5])?)
+            } else {
+                None
+            };
+            settle_claim(
+                state,
+                &participant_account,
+                wallet_info,
+                rent_payer_info,
+                &UncheckedAccount::try_from(denylist_info),
+                &ctx.accounts.program_token_account,
+                token_account_info,
+                &token_mint,
+                &ctx.accounts.token_program,
+                ctx.bumps.program_token_account,
+                receipt_accounts(
+                    state,
+                    &ctx.accounts.receipt_mint,
+                    &receipt_token_account,
+                    &wallet_info.key(),
+                )?,
+                wallet_info,
+            )?;
+            paid += 1;
+        }
+
+        msg!("Batch claim; participants paid: {}", paid);
+
+        Ok(())
+    }
+
+    // Participant votes on the release of an approved tranche, weighted by their non-cancelled contribution.
+    // Can be called by the participant while the tranche vote is open, unless denylisted as their
+    // contribution is reserved for its refund
+    pub fn vote_tranche(ctx: Context<VoteTranche>, index: u8, approve: bool) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let state = &mut ctx.accounts.state;
+        let participant = ctx.accounts.participant_account.load()?;
+        require!(state.tranche_vote_duration > 0, CustomError::VotingNotConfigured);
+        require!(!state.escrow_refunding, CustomError::EscrowRefunding);
+        require_not_denylisted(&ctx.accounts.denylist_entry)?;
+        require!(participant.amount > 0, CustomError::NoContribution);
+
+        let tranche = state.tranches.get_mut(index as usize).ok_or(CustomError::InvalidTranche)?;
+        require!(tranche.approved, CustomError::TrancheNotApproved);
+        require!(!tranche.released, CustomError::TrancheAlreadyReleased);
+        require!(now <= tranche.vote_end, CustomError::VoteClosed);
+
+        let weight = participant.amount;
+        if approve {
+            tranche.votes_for += weight;
+        } else {
+            tranche.votes_against += weight;
+        }
+
+        let vote = &mut ctx.accounts.tranche_vote;
+        vote.approve = approve;
+        vote.weight = weight;
+
+        msg!("Tranche vote cast; tranche: {}, approve: {}, weight: {}", index, approve, weight);
+
+        Ok(())
+    }
+
+    // Refund the remaining escrow pro-rata after a tranche vote has failed.
+    // Can be called by anyone once the vote has closed, only when tranche votes are configured
+    pub fn fail_tranche(ctx: Context<FailTranche>, index: u8) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let state = &mut ctx.accounts.state;
+        require!(state.tranche_vote_duration > 0, CustomError::VotingNotConfigured);
+        require!(!state.escrow_refunding, CustomError::EscrowRefunding);
+
+        let tranche = state.tranches.get(index as usize).ok_or(CustomError::InvalidTranche)?;
+        require!(tranche.approved, CustomError::TrancheNotApproved);
+        require!(!tranche.released, CustomError::TrancheAlreadyReleased);
+        require!(now > tranche.vote_end, CustomError::VoteStillOpen);
+        require!(tranche.votes_for <= tranche.votes_against, CustomError::VotePassed);
+
+        state.escrow_refunding = true;
+        state.escrow_refund_lamports = state.escrowed_lamports - state.released_lamports;
+
+        msg!("Tranche vote failed; tranche: {}, escrow to refund: {}", index, state.escrow_refund_lamports);
+
+        Ok(())
+    }
+
+    // Pays a participant's pro-rata share of a refunded escrow and closes their participant account.
+    // Can be called by the participant after claiming, once the escrow is refunding or fully released
+    pub fn settle_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let participant = ctx.accounts.participant_account.load()?;
+        require!(participant.claimed == 1, CustomError::ParticipantHasNotClaimed);
+        require!(
+            state.escrow_refunding || state.released_lamports == state.escrowed_lamports,
+            CustomError::EscrowNotSettled
+        );
+
+        if state.escrow_refunding {
+            let refund = (participant.amount as u128)
+                .checked_mul(state.escrow_refund_lamports as u128)
+                .ok_or(CustomError::ArithmeticOverflow)?
+                .checked_div(allocated_contributions(state) as u128)
+                .ok_or(CustomError::DivisionByZero)? as u64;
+
+            transfer_lamports(
+                &ctx.accounts.state.to_account_info(),
+                &ctx.accounts.participant.to_account_info(),
+                refund,
+            )?;
+
+            msg!("Escrow refunded; participant: {}, lamports: {}", participant.participant, refund);
+        }
+
+        Ok(())
+    }
+
+    // Write the immutable offer results record for disclosure (MiCA Article 10).
+    // Distribution period has started and the escrow, if any, is fully released or refunding
+    // Can be called by anyone, once
+    pub fn finalize(ctx: Context<Finalize>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(state.tokens_distributed, CustomError::TokensNotDistributed);
+        require!(!state.finalized, CustomError::AlreadyFinalized);
+        // An escrowed raise is only final once fully released or refunding
+        require!(
+            state.released_lamports == state.escrowed_lamports || state.escrow_refunding,
+            CustomError::EscrowNotSettled
+        );
+
+        let bonus_tokens = (state.active_early_investor_count + state.large_investor_count) * 1000;
+        let filled = allocated_contributions(state) - state.total_oversubscribed;
+
+        // Effective price in lamports per whole token
+        let token_price = (filled as u128)
+            .checked_mul(10u128.pow(ctx.accounts.token_mint.decimals as u32))
+            .ok_or(CustomError::ArithmeticOverflow)?
+            .checked_div(state.total_tokens as u128)
+            .ok_or(CustomError::DivisionByZero)? as u64;
+
+        let results = &mut ctx.accounts.results;
+        results.gross_raised = state.total_contributed + state.total_cancelled;
+        results.total_cancelled = state.total_cancelled;
+        let escrow_refunded = if state.escrow_refunding { state.escrow_refund_lamports } else { 0 };
+        results.total_refunded = state.total_cancelled
+            + state.total_oversubscribed
+            + state.total_denylisted
+            + escrow_refunded;
+        results.net_to_beneficiary = state.recipient_lamports - escrow_refunded;
+        results.fee_lamports = state.fee_lamports;
+        results.participant_count = state.unique_investor_count;
+        results.token_price = token_price;
+        results.tokens_allocated = state.total_tokens;
+        results.bonus_tokens = bonus_tokens;
+        results.staking_rewards = state.staking_rewards;
+        results.participation_start = state.participation_start;
+        results.participation_end = state.participation_end;
+        results.finalized_at = Clock::get()?.unix_timestamp as u64;
+
+        state.finalized = true;
+
+        msg!(
+            "Offer finalized; gross raised: {}, cancelled: {}, net to beneficiary: {}, participants: {}, token price: {}",
+            results.gross_raised,
+            results.total_cancelled,
+            results.net_to_beneficiary,
+            results.participant_count,
+            results.token_price
+        );
+
+        Ok(())
+    }
+
+    // Helper function to decrease time for participation_end for tests
+    pub fn end(ctx: Context<End>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        require!(
+            ctx.accounts.token_provider.key() == state.token_provider,
+            CustomError::UnauthorizedCaller
+        );
+        require!(!state.finalized, CustomError::AlreadyFinalized);
+        
+        state.participation_end = Clock::get()?.unix_timestamp as u64;
+        state.duration = 0;
+        record_audit_entry(
+            &mut ctx.accounts.state,
+            &mut ctx.accounts.audit_entry,
+            AuditAction::End,
+            ctx.accounts.token_provider.key(),
+            0,
+        )?;
+
+        Ok(())
+    }
+
+}
+
+// Verifies a Merkle proof using sorted-pair keccak hashing
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let mut computed = leaf;
+    for node in proof {
+        computed = if computed <= *node {
+            hashv(&[&computed, node]).to_bytes()
+        } else {
+            hashv(&[node, &computed]).to_bytes()
+        };
+    }
+    computed == root
+}
+
+// Values lamports in EUR cents using a Pyth-style (v2) price account quoting SOL in EUR
+fn lamports_to_eur_cents(oracle: &AccountInfo, lamports: u64, max_staleness: u64, now: u64) -> Result<u64> {
+    let data = oracle.try_borrow_data()?;
+    require!(data.len() >= 240, CustomError::InvalidPriceOracle);
+
+    let magic = u32::from_le_bytes(data[0..4].try_into().unwrap());
+    let expo = i32::from_le_bytes(data[20..24].try_into().unwrap());
+    let timestamp = i64::from_le_bytes(data[96..104].try_into().unwrap());
+    let price = i64::from_le_bytes(data[208..216].try_into().unwrap());
+    let status = u32::from_le_bytes(data[224..228].try_into().unwrap());
+
+    require!(magic == 0xa1b2c3d4, CustomError::InvalidPriceOracle);
+    require!(status == 1 && price > 0, CustomError::InvalidPriceOracle); // 1 = trading
+    require!(
+        now.saturating_sub(timestamp as u64) <= max_staleness,
+        CustomError::StalePrice
+    );
+
+    // cents = lamports * price * 10^expo * 100 / 10^9
+    let mut value = (lamports as u128)
+        .checked_mul(price as u128)
+        .ok_or(CustomError::ArithmeticOverflow)?
+        .checked_mul(100)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    if expo >= 0 {
+        value = value
+            .checked_mul(10u128.pow(expo as u32))
+            .ok_or(CustomError::ArithmeticOverflow)?;
+    } else {
+        value /= 10u128.pow(expo.unsigned_abs());
+    }
+
+    Ok((value / 1_000_000_000) as u64)
+}
+
+// Appends an entry to the audit log, chained to the previous entry by hash
+fn record_audit_entry(
+    state: &mut State,
+    entry: &mut AuditEntry,
+    action: AuditAction,
+    actor: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let index = state.audit_entry_count;
+
+    entry.index = index;
+    entry.action = action;
+    entry.actor = actor;
+    entry.amount = amount;
+    entry.slot = clock.slot;
+    entry.timestamp = clock.unix_timestamp;
+    entry.prev_hash = state.audit_head_hash;
+    entry.hash = hashv(&[
+        &entry.prev_hash,
+        &index.to_le_bytes(),
+        &[action as u8],
+        actor.as_ref(),
+        &amount.to_le_bytes(),
+        &entry.slot.to_le_bytes(),
+        &entry.timestamp.to_le_bytes(),
+    ]).to_bytes();
+
+    state.audit_entry_count += 1;
+    state.audit_head_hash = entry.hash;
+
+    Ok(())
+}
+
+// Rejects wallets that have a denylist entry
+fn require_not_denylisted(denylist_entry: &UncheckedAccount) -> Result<()> {
+    require!(denylist_entry.data_is_empty(), CustomError::AddressDenylisted);
+    Ok(())
+}
+
+// Copy of a participant account, none if the account does not exist
+fn read_participant(participant_account: &AccountInfo) -> Result<Option<ParticipantAccount>> {
+    if participant_account.owner != &crate::ID || participant_account.data_is_empty() {
+        return Ok(None);
+    }
+    let data = participant_account.try_borrow_data()?;
+    require!(data[..8] == ParticipantAccount::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+    Ok(Some(*bytemuck::from_bytes::<ParticipantAccount>(&data[8..ParticipantAccount::LEN])))
+}
+
+// Contributions that are allocated tokens; denylisted ones are kept for their refunds
+fn allocated_contributions(state: &State) -> u64 {
+    state.total_contributed - state.total_denylisted
+}
+
+// Pays out from the state account to the beneficiary, or splits the amount between the
+// configured beneficiaries with the remaining ones passed in order through `remaining_accounts`.
+// Rounding dust goes to the first beneficiary.
+fn pay_beneficiaries(
+    state: &mut Account<State>,
+    beneficiary: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+    amount: u64,
+) -> Result<()> {
+    if state.beneficiaries.is_empty() {
+        return transfer_lamports(&state.to_account_info(), beneficiary, amount);
+    }
+
+    require!(
+        remaining_accounts.len() == state.beneficiaries.len() - 1,
+        CustomError::UnauthorizedBeneficiary
+    );
+
+    let amounts: Vec<u64> = state.beneficiaries
+        .iter()
+        .skip(1)
+        .map(|share| (amount as u128 * share.bps as u128 / 10_000) as u64)
+        .collect();
+    let remainder = amount - amounts.iter().sum::<u64>();
+
+    let state_info = state.to_account_info();
+    transfer_lamports(&state_info, beneficiary, remainder)?;
+    for ((share, account), paid) in state.beneficiaries.iter().skip(1).zip(remaining_accounts).zip(&amounts) {
+        require!(account.key() == share.recipient, CustomError::UnauthorizedBeneficiary);
+        transfer_lamports(&state_info, account, *paid)?;
+        msg!("Beneficiary paid; beneficiary: {}, lamports: {}", share.recipient, paid);
+    }
+
+    // Record cumulative payouts for disclosure
+    state.beneficiaries[0].paid_lamports += remainder;
+    for (share, paid) in state.beneficiaries.iter_mut().skip(1).zip(amounts) {
+        share.paid_lamports += paid;
+    }
+
+    Ok(())
+}
+
+// Record a contribution of `amount` lamports paid by `funder` to the position of `owner`.
+// Caps and the priority allowlist apply to the owner; `owner_accepts_terms` records the owner's acceptance
+// of the current white paper on the position
+#[allow(clippy::too_many_arguments)]
+fn record_participation<'info>(
+    state: &mut Account<'info, State>,
+    participant_account: &AccountLoader<'info, ParticipantAccount>,
+    funder: &AccountInfo<'info>,
+    owner: &Pubkey,
+    rent_payer: &Pubkey,
+    denylist_entry: &UncheckedAccount<'info>,
+    price_oracle: &Option<UncheckedAccount<'info>>,
+    amount: u64,
+    max_allocation: u64,
+    proof: &[[u8; 32]],
+    terms_hash: [u8; 32],
+    owner_accepts_terms: bool,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    require!(state.participation_active, CustomError::NotActive);
+    require!(now <= state.participation_end, CustomError::ParticipationClosed);
+    require_not_denylisted(denylist_entry)?;
+    require!(
+        state.oversubscription_enabled || state.total_contributed + amount <= state.raise_cap,
+        CustomError::RaiseCapExceeded
+    );
+
+    require!(terms_hash == state.white_paper_hash, CustomError::TermsMismatch);
+
+    // The participant account is created on first use; an existing one must belong to the owner
+    let is_new = participant_account.as_ref().try_borrow_data()?[..8] == [0u8; 8];
+    let mut participant = if is_new {
+        let mut participant = participant_account.load_init()?;
+        participant.participant = *owner;
+        participant.rent_payer = *rent_payer;
+        participant
+    } else {
+        participant_account.load_mut()?
+    };
+    require!(participant.participant == *owner, CustomError::UnauthorizedCaller);
+
+    // Priority round: only allowlisted wallets, up to their allocation
+    if state.merkle_root != [0u8; 32] && now < state.priority_end {
+        let leaf = hashv(&[
+            owner.as_ref(),
+            &max_allocation.to_le_bytes(),
+        ]).to_bytes();
+        require!(
+            verify_merkle_proof(proof, state.merkle_root, leaf),
+            CustomError::NotAllowlisted
+        );
+        require!(
+            max_allocation == 0 || participant.amount + amount <= max_allocation,
+            CustomError::AllocationExceeded
+        );
+    }
+    
+    // Add check for maximum investment cap (250 SOL = 250_000_000_000 lamports)
+    require!(
+        participant.amount + amount <= 250_000_000_000,
+        CustomError::MaxContributionExceeded
+    );
+
+    // EUR cap: value the contribution at the current oracle price
+    if state.eur_cap_cents > 0 {
+        let oracle = price_oracle.as_ref().ok_or(CustomError::InvalidPriceOracle)?;
+        require!(oracle.key() == state.eur_oracle, CustomError::InvalidPriceOracle);
+
+        let amount_eur_cents = lamports_to_eur_cents(oracle, amount, state.oracle_max_staleness, now)?;
+        state.total_contributed_eur_cents = state.total_contributed_eur_cents
+            .checked_add(amount_eur_cents)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        require!(
+            state.total_contributed_eur_cents <= state.eur_cap_cents,
+            CustomError::EurCapExceeded
+        );
+    }
+
+    // A cancelled participant coming back is not a new investor
+    if participant.amount == 0 && participant.cancelled_amount == 0 {
+        state.unique_investor_count += 1;
+        // Set early investor flag if they're among first 100
+        if state.unique_investor_count <= 100 {
+            participant.is_early_investor = 1;
+            state.active_early_investor_count += 1;
+        }
+    }
+
+    let was_large_investor = participant.amount >= 100_000_000_000;
+    participant.amount += amount;
+    let is_large_investor = participant.amount >= 100_000_000_000;
+
+    // Update large investor count if status changed
+    if !was_large_investor && is_large_investor {
+        state.large_investor_count += 1;
+    }
+
+    participant.participation_time = now;
+    if owner_accepts_terms {
+        participant.accepted_terms_version = state.white_paper_version;
+    }
+    state.total_contributed = state.total_contributed
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    // Perform the transfer using the System Program
+    let transfer_instruction = system_instruction::transfer(
+        funder.key,
+        &state.key(),
+        amount,
+    );
+
+    invoke(
+        &transfer_instruction,
+        &[
+            funder.clone(),
+            state.to_account_info(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+// Message a participant signs to authorize a relayed participation
+pub fn intent_message(
+    sale: &Pubkey,
+    participant: &Pubkey,
+    amount: u64,
+    max_allocation: u64,
+    terms_hash: &[u8; 32],
+    nonce: u64,
+    expiry: u64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(INTENT_DOMAIN.len() + 32 * 4 + 8 * 4);
+    message.extend_from_slice(INTENT_DOMAIN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(sale.as_ref());
+    message.extend_from_slice(participant.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&max_allocation.to_le_bytes());
+    message.extend_from_slice(terms_hash);
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+// Check that the previous instruction verified `signer`'s signature over `message` with the ed25519 program
+fn verify_ed25519_intent(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    require!(current > 0, CustomError::InvalidIntent);
+    let ix = load_instruction_at_checked(current as usize - 1, instructions)?;
+    require!(ix.program_id == ed25519_program::ID, CustomError::InvalidIntent);
+
+    // Layout: signature count, padding, then one offsets record of seven u16 values
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, CustomError::InvalidIntent);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let public_key_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    // Signature, public key and message must all be in the ed25519 instruction itself
+    require!(
+        read_u16(4) == u16::MAX && read_u16(8) == u16::MAX && read_u16(14) == u16::MAX,
+        CustomError::InvalidIntent
+    );
+
+    let public_key = data.get(public_key_offset..public_key_offset + 32).ok_or(CustomError::InvalidIntent)?;
+    let signed_message = data.get(message_offset..message_offset + message_size).ok_or(CustomError::InvalidIntent)?;
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        CustomError::InvalidIntent
+    );
+
+    Ok(())
+}
+
+// Automatic safeguarding: move the held balance above the reserve once it exceeds the threshold
+fn auto_safeguard(state: &mut Account<State>, safeguarding_account: &Option<UncheckedAccount>) -> Result<()> {
+    let threshold = state.auto_safeguard_threshold;
+    if threshold > 0 {
+        let safeguarding_account = safeguarding_account
+            .as_ref()
+            .ok_or(CustomError::UnauthorizedSafeguardingAccount)?;
+        require!(
+            safeguarding_account.key() == state.safeguarding_account,
+            CustomError::UnauthorizedSafeguardingAccount
+        );
+
+        let state_info = state.to_account_info();
+        let rent_exempt_balance = Rent::get()?.minimum_balance(state_info.data_len());
+        let held = state_info.lamports().saturating_sub(rent_exempt_balance);
+        if held > threshold {
+            // Always keep enough for the refunds of cancelled, denylisted and oversubscribed contributions
+            let reserve = state.auto_safeguard_reserve.max(refund_reserve(state));
+            let excess = held.saturating_sub(reserve);
+            if excess > 0 {
+                transfer_lamports(&state_info, safeguarding_account, excess)?;
+                state.total_safeguarded += excess;
+                // Recorded in the audit log by the token provider, see `record_auto_safeguard`
+                state.unrecorded_auto_safeguarded += excess;
+
+                msg!(
+                    "Funds safeguarded automatically; amount: {}, total safeguarded: {}",
+                    excess,
+                    state.total_safeguarded
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Lamports that must stay on the contract for refunds: cancelled and denylisted contributions,
+// and contributions above the raise cap in oversubscription mode
+fn refund_reserve(state: &State) -> u64 {
+    let oversubscribed = if state.oversubscription_enabled {
+        allocated_contributions(state).saturating_sub(state.raise_cap)
+    } else {
+        0
+    };
+    state.total_cancelled + state.total_denylisted + oversubscribed
+}
+
+// Receipt mint and the participant's receipt token account, required once receipts are enabled
+fn receipt_accounts<'a, 'info>(
+    state: &State,
+    receipt_mint: &'a Option<Account<'info, Mint>>,
+    receipt_token_account: &'a Option<Account<'info, TokenAccount>>,
+    owner: &Pubkey,
+) -> Result<Option<(&'a Account<'info, Mint>, &'a Account<'info, TokenAccount>)>> {
+    if state.receipt_mint == Pubkey::default() {
+        return Ok(None);
+    }
+    let receipt_mint = receipt_mint.as_ref().ok_or(CustomError::InvalidReceiptAccount)?;
+    let receipt_token_account = receipt_token_account.as_ref().ok_or(CustomError::InvalidReceiptAccount)?;
+    require!(
+        receipt_mint.key() == state.receipt_mint
+            && receipt_token_account.mint == state.receipt_mint
+            && receipt_token_account.owner == *owner,
+        CustomError::InvalidReceiptAccount
+    );
+    Ok(Some((receipt_mint, receipt_token_account)))
+}
+
+// Wallet whose receipts are redeemed on claim: the caller for transferable receipts, the participant otherwise
+fn receipt_holder<'a, 'info>(
+    state: &State,
+    participant: &'a AccountInfo<'info>,
+    caller: &'a AccountInfo<'info>,
+) -> &'a AccountInfo<'info> {
+    if state.receipts_transferable {
+        caller
+    } else {
+        participant
+    }
+}
+
+// Non-transferable receipts are frozen between participations
+fn thaw_receipts<'info>(
+    state: &State,
+    receipt_mint: &Account<'info, Mint>,
+    receipt_token_account: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    if receipt_token_account.is_frozen() {
+        token::thaw_account(CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::ThawAccount {
+                account: receipt_token_account.to_account_info(),
+                mint: receipt_mint.to_account_info(),
+                authority: receipt_mint.to_account_info(),
+            },
+            &[&[b"receipt_mint", &[state.receipt_mint_bump]]],
+        ))?;
+    }
+    Ok(())
+}
+
+// Burn non-transferable receipts as the delegate approved at participation, and transferable ones
+// with the signature of their holder
+fn burn_receipts<'info>(
+    state: &State,
+    receipt_mint: &Account<'info, Mint>,
+    receipt_token_account: &Account<'info, TokenAccount>,
+    holder: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    thaw_receipts(state, receipt_mint, receipt_token_account, token_program)?;
+    let authority = if state.receipts_transferable {
+        holder.clone()
+    } else {
+        receipt_mint.to_account_info()
+    };
+    token::burn(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::Burn {
+                mint: receipt_mint.to_account_info(),
+                from: receipt_token_account.to_account_info(),
+                authority,
+            },
+            &[&[b"receipt_mint", &[state.receipt_mint_bump]]],
+        ),
+        amount,
+    )
+}
+
+// Token allocation of a non-cancelled participant, including their bonuses
+fn participant_token_share(state: &State, participant: &ParticipantAccount) -> Result<u64> {
+    // Calculate total possible bonus tokens
+    let total_bonus_tokens = 
+        (state.active_early_investor_count as u128 * 1000) + // early investor bonuses
+        (state.large_investor_count as u128  * 1000);         // large investor bonuses
+
+    // Adjust total tokens by subtracting reserved bonus tokens
+    let distributable_tokens = (state.total_tokens as u128).saturating_sub(total_bonus_tokens);
+
+    // Calculate base share from remaining tokens
+    let participant_amount = participant.amount as u128;
+    let total_contributed = allocated_contributions(state) as u128;
+    
+    let mut share = distributable_tokens
+        .checked_mul(participant_amount)
+        .ok_or(CustomError::ArithmeticOverflow)?
+        .checked_div(total_contributed)
+        .ok_or(CustomError::DivisionByZero)? as u64;
+    
+    // Add early investor bonus
+    if participant.is_early_investor == 1 {
+        share += 1000;
+    }
+
+    // Add large investor bonus
+    if participant.amount >= 100_000_000_000 {
+        share += 1000;
+    }
+
+    Ok(share)
+}
+
+// Refund a participant's cancelled amount and pay the token allocation of their active amount, or refund
+// everything if they are denylisted, then close their participant account unless it is still needed
+// for a tranche vote
+#[allow(clippy::too_many_arguments)]
+fn settle_claim<'info>(
+    state: &Account<'info, State>,
+    participant_account: &AccountLoader<'info, ParticipantAccount>,
+    participant_info: &AccountInfo<'info>,
+    rent_payer: &AccountInfo<'info>,
+    denylist_entry: &UncheckedAccount<'info>,
+    program_token_account: &Account<'info, TokenAccount>,
+    participant_token_account: &AccountInfo<'info>,
+    token_mint: &Pubkey,
+    token_program: &Program<'info, Token>,
+    program_token_account_bump: u8,
+    receipt: Option<(&Account<'info, Mint>, &Account<'info, TokenAccount>)>,
+    receipt_holder: &AccountInfo<'info>,
+) -> Result<()> {
+    let mut participant = participant_account.load_mut()?;
+    require!(state.tokens_distributed, CustomError::TokensNotDistributed);
+    require!(participant.participation_time > 0, CustomError::NeverParticipated);
+    require!(participant.claimed == 0, CustomError::AlreadyClaimed);
+
+    // Denylisted wallets may only be refunded, and only once the release is allowed
+    let denylisted = !denylist_entry.data_is_empty();
+    let mut reserved = 0;
+    if denylisted {
+        let data = denylist_entry.try_borrow_data()?;
+        let entry = DenylistEntry::try_deserialize(&mut &data[..])?;
+        require!(entry.refund_allowed, CustomError::AddressDenylisted);
+        reserved = entry.reserved_lamports;
+    }
+
+    // Receipts of the active amount are redeemed; those of the cancelled amount were burned on cancel
+    if let Some((receipt_mint, receipt_token_account)) = receipt {
+        if participant.amount > 0 {
+            burn_receipts(
+                state,
+                receipt_mint,
+                receipt_token_account,
+                receipt_holder,
+                token_program,
+                participant.amount,
+            )?;
+        }
+    }
+
+    // Refund the cancelled amount and, for denylisted participants, the contribution reserved when
+    // they were listed; a contribution paid out before the listing is not refunded
+    let refund = participant.cancelled_amount + reserved;
+    if refund > 0 {
+        transfer_lamports(&state.to_account_info(), participant_info, refund)?;
+    }
+
+    if participant.amount > 0 && !denylisted {
+        let share = participant_token_share(state, &participant)?;
+
+        let seeds = &[token_mint.as_ref(), &[program_token_account_bump]];
+        let signer = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::Transfer {
+                from: program_token_account.to_account_info(),
+                to: participant_token_account.clone(),
+                authority: program_token_account.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(cpi_ctx, share)?;
+
+        // Refund the unfilled portion of an oversubscribed contribution
+        if state.total_oversubscribed > 0 {
+            let refund = (participant.amount as u128)
+                .checked_mul(state.total_oversubscribed as u128)
+                .ok_or(CustomError::ArithmeticOverflow)?
+                .checked_div(allocated_contributions(state) as u128)
+                .ok_or(CustomError::DivisionByZero)? as u64;
+
+            transfer_lamports(
+                &state.to_account_info(),
+                participant_info,
+                refund,
+            )?;
+        }
+    }
+
+    // Voters keep their account until their share of the escrow is settled
+    let keep_open = state.tranche_vote_duration > 0 && participant.amount > 0 && !denylisted;
+    if keep_open {
+        participant.claimed = 1;
+    }
+    drop(participant);
+    if !keep_open {
+        participant_account.close(rent_payer.clone())?;
+    }
+
+    Ok(())
+}
+
+// Moves lamports out of a program-owned account
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let from_lamports = from.lamports();
+
+    // Ensure the source has enough lamports for the transfer
+    require!(from_lamports >= amount, CustomError::InsufficientRefundBalance);
+
+    // Transfer lamports using safe arithmetic operations
+    let new_from_lamports = from_lamports.checked_sub(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    let new_to_lamports = to.lamports()
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+
+    // Update lamport balances
+    **from.try_borrow_mut_lamports()? = new_from_lamports;
+    **to.try_borrow_mut_lamports()? = new_to_lamports;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + State::INIT_SPACE,
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Account<'info, State>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct State {
+    pub initialized: bool,
+    pub participation_active: bool,
+    pub participation_end: u64,
+    pub raise_cap: u64,
+    pub total_contributed: u64,
+    pub total_tokens: u64,
+    pub tokens_distributed: bool,
+    pub recipient_lamports: u64,
+    pub fee_lamports: u64, // Platform fee paid out on distribution
+    pub token_provider: Pubkey,
+    pub beneficiary: Pubkey,
     pub safeguarding_account: Pubkey,
     pub token_mint: Pubkey,
     pub total_cancelled: u64, // Track total amount of SOL to be refunded
@@ -385,115 +2406,733 @@ pub struct State {
     pub unique_investor_count: u64, // Track number of first-time investors
     pub active_early_investor_count: u64, // Track number of non-cancelled early investors
     pub duration: u64,  // Store the participation period duration
+    pub oversubscription_enabled: bool, // Accept contributions beyond the raise cap
+    pub total_oversubscribed: u64, // Contributions above the raise cap, refunded pro-rata on claim
+    pub merkle_root: [u8; 32], // Allowlist root for the priority round, zero when disabled
+    pub priority_end: u64, // End of the priority window
+    pub denylist_authority: Pubkey, // Maintains the denylist of sanctioned addresses
+    pub fee_bps: u16, // Platform fee in basis points of the payout
+    pub fee_recipient: Pubkey,
+    #[max_len(MAX_BENEFICIARIES)]
+    pub beneficiaries: Vec<BeneficiaryShare>, // Optional payout split, empty when `beneficiary` takes all
+    pub escrow_custodian: Pubkey, // Approves the release of escrowed tranches
+    pub escrowed_lamports: u64, // Payout held in escrow at distribution
+    pub released_lamports: u64, // Escrowed payout released so far
+    #[max_len(MAX_TRANCHES)]
+    pub tranches: Vec<Tranche>, // Optional release schedule, empty when the payout is not escrowed
+    pub tranche_vote_duration: u64, // Participant vote length per tranche, zero when voting is disabled
+    pub escrow_refunding: bool, // Set once a tranche vote has failed
+    pub escrow_refund_lamports: u64, // Escrow left to refund pro-rata after a failed vote
+    pub white_paper_version: u16, // Number of published white paper versions
+    pub white_paper_hash: [u8; 32], // SHA-256 of the current white paper
+    #[max_len(MAX_URI_LEN)]
+    pub white_paper_uri: String,
+    pub participation_start: u64, // Set on activation
+    pub finalized: bool, // Results record written, totals are frozen
+    pub eur_cap_cents: u64, // Optional offer cap in EUR cents, zero when disabled
+    pub eur_oracle: Pubkey, // Pyth-style SOL/EUR price account
+    pub oracle_max_staleness: u64, // Maximum price age in seconds
+    pub total_contributed_eur_cents: u64, // EUR value of contributions at the time they were made
+    pub audit_entry_count: u64, // Number of audit log entries
+    pub audit_head_hash: [u8; 32], // Hash of the latest audit log entry
+    pub total_safeguarded: u64, // Cumulative amount moved to the safeguarding account
+    pub total_returned_from_safeguard: u64, // Cumulative amount returned by the safeguarding account
+    pub auto_safeguard_threshold: u64, // Held balance that triggers automatic safeguarding, zero when disabled
+    pub auto_safeguard_reserve: u64, // Balance kept on the contract when safeguarding automatically
+    pub stake_vote_account: Pubkey, // Validator for staking safeguarded SOL, default when disabled
+    pub stake_active: bool, // Stake account holds funds that must be withdrawn before distribution
+    pub staked_lamports: u64,
+    pub stake_rent_lamports: u64, // Stake account rent paid by the token provider
+    pub staking_rewards: u64, // Attributed to the beneficiary
+    pub receipt_mint: Pubkey, // Receipt token mint, default when receipts are disabled
+    pub receipt_mint_bump: u8,
+    pub receipts_transferable: bool,
+    pub total_denylisted: u64, // Contributions of wallets denylisted before distribution, kept for their refunds
+    pub unrecorded_auto_safeguarded: u64, // Safeguarded automatically and not yet recorded in the audit log
+}
+
+pub const MAX_URI_LEN: usize = 200;
+
+pub const MAX_TRANCHES: usize = 10;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Tranche {
+    pub bps: u16,
+    pub unlock_time: u64, // Earliest release time
+    pub approved: bool,
+    pub released: bool,
+    pub released_lamports: u64,
+    pub vote_end: u64,
+    pub votes_for: u64,
+    pub votes_against: u64,
+}
+
+pub const MAX_BENEFICIARIES: usize = 10;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct BeneficiaryShare {
+    pub recipient: Pubkey,
+    pub bps: u16,
+    pub paid_lamports: u64, // Recorded on distribution for disclosure
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub state: Account<'info, State>,
+    #[account(mut)]
+    pub token_authority: Signer<'info>,
+    #[account(
+        init, // Safe because this is callable only once
+        payer = token_authority,
+        seeds = [  token_mint.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = program_token_account
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub from_token_account: Account<'info, TokenAccount>,
+    /// CHECK: This is the mint of the token being deposited
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = token_authority,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    pub audit_entry: Account<'info, AuditEntry>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Configure<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct AuditEntry {
+    pub index: u64,
+    pub action: AuditAction,
+    pub actor: Pubkey,
+    pub amount: u64, // Lamports or tokens moved, duration for activation, zero otherwise
+    pub slot: u64,
+    pub timestamp: i64,
+    pub prev_hash: [u8; 32],
+    pub hash: [u8; 32], // keccak(prev_hash, index, action, actor, amount, slot, timestamp)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum AuditAction {
+    Deposit,
+    Configure,
+    PublishWhitePaper,
+    Activate,
+    AddToDenylist,
+    RemoveFromDenylist,
+    AllowDenylistedRefund,
+    Safeguard,
+    Distribute,
+    ApproveTranche,
+    ReleaseTranche,
+    End,
+    ReturnFromSafeguard,
+    StakeSafeguarded,
+    DeactivateStake,
+    WithdrawStake,
+    AutoSafeguard,
+}
+
+#[derive(Accounts)]
+pub struct PublishWhitePaper<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    #[account(
+        init, // Versions are append-only
+        payer = token_provider,
+        space = 8 + WhitePaperVersion::INIT_SPACE,
+        seeds = [b"white_paper".as_ref(), &(state.white_paper_version + 1).to_le_bytes()],
+        bump
+    )]
+    white_paper: Account<'info, WhitePaperVersion>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WhitePaperVersion {
+    pub version: u16,
+    pub hash: [u8; 32], // SHA-256 of the white paper document
+    #[max_len(MAX_URI_LEN)]
+    pub uri: String,
+    pub published_at: u64,
+}
+
+#[derive(Accounts)]
+pub struct Activate<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitParticipant<'info> {
+    participant: Signer<'info>,
+    // May differ from the participant to sponsor the account rent
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = ParticipantAccount::LEN,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[account(zero_copy)]
+#[repr(C, packed)]
+pub struct ParticipantAccount {
+    pub amount: u64,
+    pub participation_time: u64,
+    pub participant: Pubkey,
+    pub cancelled: u8, // Set once part of the contribution has been cancelled
+    pub is_early_investor: u8,
+    pub claimed: u8,
+    pub accepted_terms_version: u16, // White paper version accepted at the latest participation
+    pub _padding: [u8; 3],
+    pub rent_payer: Pubkey, // Paid the account rent and gets it back when the account is closed
+    pub claim_destination: Pubkey, // Wallet receiving the tokens, default for the participant
+    pub claim_delegate: Pubkey, // May claim on behalf of the participant, default when unset
+    pub cancelled_amount: u64, // Refunded on claim, next to the tokens for the active amount
+}
+
+impl ParticipantAccount {
+    pub const LEN: usize = 
+        8 +   // discriminator
+        8 +   // amount
+        8 +   // participation_time
+        32 +  // participant pubkey
+        1 +   // cancelled boolean
+        1 +   // is_early_investor boolean
+        1 +   // claimed boolean
+        2 +   // accepted_terms_version
+        3 +   // padding
+        32 +  // rent_payer pubkey
+        32 +  // claim_destination pubkey
+        32 +  // claim_delegate pubkey
+        8;    // cancelled_amount
+
+    // Owner of the token account that receives the allocation when someone else claims
+    pub fn token_owner(&self) -> Pubkey {
+        if self.claim_destination == Pubkey::default() {
+            self.participant
+        } else {
+            self.claim_destination
+        }
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddToDenylist<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    denylist_authority: Signer<'info>,
+    #[account(
+        init,
+        payer = denylist_authority,
+        space = 8 + DenylistEntry::INIT_SPACE,
+        seeds = [b"denylist", wallet.as_ref()],
+        bump
+    )]
+    denylist_entry: Account<'info, DenylistEntry>,
+    /// CHECK: Read only if it exists; the PDA can only be created by this program
+    #[account(seeds = [b"participant", wallet.as_ref()], bump)]
+    participant_account: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = denylist_authority,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RemoveFromDenylist<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    denylist_authority: Signer<'info>,
+    #[account(
+        mut,
+        close = denylist_authority,
+        seeds = [b"denylist", wallet.as_ref()],
+        bump
+    )]
+    denylist_entry: Account<'info, DenylistEntry>,
+    #[account(
+        init,
+        payer = denylist_authority,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct UpdateDenylist<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    denylist_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"denylist", wallet.as_ref()],
+        bump
+    )]
+    denylist_entry: Account<'info, DenylistEntry>,
+    #[account(
+        init,
+        payer = denylist_authority,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct DenylistEntry {
+    pub wallet: Pubkey,
+    pub listed_at: u64,
+    pub refund_allowed: bool, // Set once a court or regulator allows the refund
+    pub reserved_lamports: u64, // Contribution kept out of the payout for the refund
+    pub early_investor: bool, // Early investor bonus no longer set aside, restored on removal
+    pub large_investor: bool, // Large investor bonus no longer set aside, restored on removal
+}
+
+#[derive(Accounts)]
+pub struct Participate<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    participant: Signer<'info>,
+    // May differ from the participant to sponsor the account rent
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = ParticipantAccount::LEN,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    /// CHECK: Verified against the configured oracle; only required when a EUR cap is set
+    price_oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Verified against the state account; only required when automatic safeguarding is set
+    #[account(mut)]
+    safeguarding_account: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
+    /// Participant's receipt token account, required when receipts are enabled
+    #[account(mut)]
+    receipt_token_account: Option<Account<'info, TokenAccount>>,
+    token_program: Option<Program<'info, Token>>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_owner: Pubkey)]
+pub struct TransferPosition<'info> {
+    state: Account<'info, State>,
+    participant: Signer<'info>,
+    // Pays the rent of the new participant account
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump,
+        has_one = participant,
+        has_one = rent_payer @ CustomError::InvalidRentPayer
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Verified against the participant account
+    #[account(mut)]
+    rent_payer: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = ParticipantAccount::LEN,
+        seeds = [b"participant", new_owner.as_ref()],
+        bump
+    )]
+    new_participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", new_owner.as_ref()], bump)]
+    new_owner_denylist_entry: UncheckedAccount<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureReceipts<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        seeds = [b"receipt_mint"],
+        bump,
+        mint::decimals = 9, // one receipt per contributed SOL
+        mint::authority = receipt_mint,
+        mint::freeze_authority = receipt_mint,
+    )]
+    receipt_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ParticipateFor<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    // Pays the contribution and the account rent
+    #[account(mut)]
+    funder: Signer<'info>,
+    /// CHECK: Owner of the position; it does not need to sign
+    participant: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = ParticipantAccount::LEN,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", funder.key().as_ref()], bump)]
+    funder_denylist_entry: UncheckedAccount<'info>,
+    /// CHECK: Verified against the configured oracle; only required when a EUR cap is set
+    price_oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Verified against the state account; only required when automatic safeguarding is set
+    #[account(mut)]
+    safeguarding_account: Option<UncheckedAccount<'info>>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ParticipateWithIntent<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    // Submits the intent and pays the fees, the account rent and the contribution
+    #[account(mut)]
+    relayer: Signer<'info>,
+    /// CHECK: Signer of the intent, verified through the ed25519 instruction
+    participant: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = ParticipantAccount::LEN,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", relayer.key().as_ref()], bump)]
+    relayer_denylist_entry: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + IntentNonce::INIT_SPACE,
+        seeds = [b"intent_nonce", participant.key().as_ref()],
+        bump
+    )]
+    intent_nonce: Account<'info, IntentNonce>,
+    /// CHECK: Verified against the configured oracle; only required when a EUR cap is set
+    price_oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Verified against the state account; only required when automatic safeguarding is set
+    #[account(mut)]
+    safeguarding_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: Instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions: UncheckedAccount<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct IntentNonce {
+    pub next: u64, // Next nonce accepted for the participant's relayed intents
+}
+
+// Prefix of signed participation intents
+pub const INTENT_DOMAIN: &[u8] = b"swan:participation-intent";
+
+#[derive(Accounts)]
+pub struct Cancel<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    participant: Signer<'info>,
+    #[account(mut, has_one = participant)]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
+    /// Participant's receipt token account, required when receipts are enabled
+    #[account(mut)]
+    receipt_token_account: Option<Account<'info, TokenAccount>>,
+    token_program: Option<Program<'info, Token>>,
 }
-
 #[derive(Accounts)]
-pub struct Deposit<'info> {
+pub struct Distribute<'info> {
     #[account(mut)]
-    pub state: Account<'info, State>,
+    state: Account<'info, State>,
     #[account(mut)]
-    pub token_authority: Signer<'info>,
+    payer: Signer<'info>,
+    #[account(mut)]
+    /// CHECK: Beneficiary account is verified in the state account
+    beneficiary: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Fee recipient is verified against the state account
+    fee_recipient: AccountInfo<'info>,
     #[account(
-        init, // Safe because this is callable only once
-        payer = token_authority,
-        seeds = [  token_mint.key().as_ref()],
-        bump,
-        token::mint = token_mint,
-        token::authority = program_token_account
+        init,
+        payer = payer,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
     )]
-    pub program_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub from_token_account: Account<'info, TokenAccount>,
-    /// CHECK: This is the mint of the token being deposited
-    pub token_mint: Account<'info, Mint>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Activate<'info> {
+pub struct ApproveTranche<'info> {
     #[account(mut)]
     state: Account<'info, State>,
-    token_provider: Signer<'info>,
+    #[account(mut)]
+    custodian: Signer<'info>,
+    #[account(
+        init,
+        payer = custodian,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct InitParticipant<'info> {
+pub struct ReleaseTranche<'info> {
     #[account(mut)]
-    participant: Signer<'info>,
+    state: Account<'info, State>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(mut)]
+    /// CHECK: Beneficiary account is verified against the state account
+    beneficiary: AccountInfo<'info>,
     #[account(
         init,
-        payer = participant,
-        space = ParticipantAccount::LEN,
-        seeds = [b"participant", participant.key().as_ref()],
+        payer = payer,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
         bump
     )]
-    participant_account: AccountLoader<'info, ParticipantAccount>,
+    audit_entry: Account<'info, AuditEntry>,
     system_program: Program<'info, System>,
 }
 
-#[account(zero_copy)]
-#[repr(C, packed)]
-pub struct ParticipantAccount {
-    pub amount: u64,
-    pub participation_time: u64,
-    pub participant: Pubkey,
-    pub cancelled: u8,
-    pub is_early_investor: u8,
-    pub _padding: [u8; 6],
-}
-
-impl ParticipantAccount {
-    pub const LEN: usize = 
-        8 +   // discriminator
-        8 +   // amount
-        8 +   // participation_time
-        32 +  // participant pubkey
-        1 +   // cancelled boolean
-        1 +   // is_early_investor boolean
-        6;    // padding
-}
-
 #[derive(Accounts)]
-pub struct Participate<'info> {
+#[instruction(index: u8)]
+pub struct VoteTranche<'info> {
     #[account(mut)]
     state: Account<'info, State>,
     #[account(mut)]
     participant: Signer<'info>,
     #[account(
-        mut,
         seeds = [b"participant", participant.key().as_ref()],
         bump,
         has_one = participant
     )]
     participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Only checked for existence; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    #[account(
+        init, // Fails if the participant has already voted on this tranche
+        payer = participant,
+        space = 8 + TrancheVote::INIT_SPACE,
+        seeds = [b"vote".as_ref(), &[index], participant.key().as_ref()],
+        bump
+    )]
+    tranche_vote: Account<'info, TrancheVote>,
     system_program: Program<'info, System>,
 }
 
+#[account]
+#[derive(InitSpace)]
+pub struct TrancheVote {
+    pub approve: bool,
+    pub weight: u64,
+}
+
 #[derive(Accounts)]
-pub struct Cancel<'info> {
+pub struct FailTranche<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+}
+
+#[derive(Accounts)]
+pub struct SettleEscrow<'info> {
     #[account(mut)]
     state: Account<'info, State>,
     #[account(mut)]
     participant: Signer<'info>,
-    #[account(mut, has_one = participant)]
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump,
+        has_one = participant,
+        has_one = rent_payer @ CustomError::InvalidRentPayer
+    )]
     participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Verified against the participant account
+    #[account(mut)]
+    rent_payer: UncheckedAccount<'info>,
 }
+
 #[derive(Accounts)]
-pub struct Distribute<'info> {
+pub struct Finalize<'info> {
     #[account(mut)]
     state: Account<'info, State>,
     #[account(mut)]
-    /// CHECK: Beneficiary account is verified in the state account
-    beneficiary: AccountInfo<'info>,
+    payer: Signer<'info>,
+    #[account(
+        init, // Results are written once and never updated
+        payer = payer,
+        space = 8 + OfferResults::INIT_SPACE,
+        seeds = [b"results"],
+        bump
+    )]
+    results: Account<'info, OfferResults>,
+    #[account(constraint = token_mint.key() == state.token_mint @ CustomError::InvalidTokenMint)]
+    token_mint: Account<'info, Mint>,
+    system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct OfferResults {
+    pub gross_raised: u64, // All contributions received, including cancelled ones
+    pub total_cancelled: u64,
+    pub total_refunded: u64, // Cancellation, oversubscription, denylist and escrow refunds
+    pub net_to_beneficiary: u64,
+    pub fee_lamports: u64,
+    pub participant_count: u64,
+    pub token_price: u64, // Lamports per whole token
+    pub tokens_allocated: u64, // Including bonus tokens
+    pub bonus_tokens: u64,
+    pub participation_start: u64,
+    pub participation_end: u64,
+    pub finalized_at: u64,
+    pub staking_rewards: u64, // Included in the beneficiary payout
 }
 
 #[derive(Accounts)]
 pub struct End<'info> {
     #[account(mut)]
     state: Account<'info, State>,
+    #[account(mut)]
     token_provider: Signer<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -505,12 +3144,18 @@ pub struct Claim<'info> {
     participant: Signer<'info>,
     #[account(
         mut,
-        close = participant,
         seeds = [b"participant", participant.key().as_ref()],
         bump,
-        has_one = participant
+        has_one = participant,
+        has_one = rent_payer @ CustomError::InvalidRentPayer
     )]
     participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Verified against the participant account
+    #[account(mut)]
+    rent_payer: UncheckedAccount<'info>,
+    /// CHECK: Deserialized only if it exists; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
     #[account(
         mut,
         seeds = [ token_mint.key().as_ref() ],
@@ -521,23 +3166,271 @@ pub struct Claim<'info> {
         mut,
         constraint = participant_token_account.mint == token_mint.key(),
         constraint = participant_token_account.owner == participant.key()
+            || participant_token_account.owner == participant_account.load()?.claim_destination
+            @ CustomError::InvalidClaimDestination
+    )]
+    participant_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    token_mint: Account<'info, Mint>,
+    token_program: Program<'info, Token>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
+    /// Participant's receipt token account, required when receipts are enabled
+    #[account(mut)]
+    receipt_token_account: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimOptions<'info> {
+    participant: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump,
+        has_one = participant
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+}
+
+#[derive(Accounts)]
+pub struct DelegatedClaim<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(
+        constraint = delegate.key() == participant_account.load()?.claim_delegate @ CustomError::UnauthorizedCaller
+    )]
+    delegate: Signer<'info>,
+    /// CHECK: Receives refunds; verified against the participant account
+    #[account(mut)]
+    participant: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump,
+        has_one = participant,
+        has_one = rent_payer @ CustomError::InvalidRentPayer
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Verified against the participant account
+    #[account(mut)]
+    rent_payer: UncheckedAccount<'info>,
+    /// CHECK: Deserialized only if it exists; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [ token_mint.key().as_ref() ],
+        bump,
+    )]
+    program_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = participant_token_account.mint == token_mint.key(),
+        constraint = participant_token_account.owner == participant_account.load()?.token_owner()
+            @ CustomError::InvalidClaimDestination
+    )]
+    participant_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    token_mint: Account<'info, Mint>,
+    token_program: Program<'info, Token>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
+    /// Participant's receipt token account, required when receipts are enabled;
+    /// the delegate's when receipts are transferable
+    #[account(mut)]
+    receipt_token_account: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct PushClaim<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK: Receives refunds and owns the token account; verified against the participant account
+    #[account(mut)]
+    participant: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"participant", participant.key().as_ref()],
+        bump,
+        has_one = participant,
+        has_one = rent_payer @ CustomError::InvalidRentPayer
+    )]
+    participant_account: AccountLoader<'info, ParticipantAccount>,
+    /// CHECK: Verified against the participant account
+    #[account(mut)]
+    rent_payer: UncheckedAccount<'info>,
+    /// CHECK: Deserialized only if it exists; the PDA can only be created by this program
+    #[account(seeds = [b"denylist", participant.key().as_ref()], bump)]
+    denylist_entry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [ token_mint.key().as_ref() ],
+        bump,
+    )]
+    program_token_account: Account<'info, TokenAccount>,
+    /// CHECK: The participant or their claim destination
+    #[account(
+        constraint = token_owner.key() == participant_account.load()?.token_owner() @ CustomError::InvalidClaimDestination
+    )]
+    token_owner: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = token_owner
     )]
     participant_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     token_mint: Account<'info, Mint>,
     token_program: Program<'info, Token>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
+    /// Participant's receipt token account, required when receipts are enabled;
+    /// the payer's when receipts are transferable
+    #[account(mut)]
+    receipt_token_account: Option<Account<'info, TokenAccount>>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BatchClaim<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(
+        mut,
+        seeds = [ token_mint.key().as_ref() ],
+        bump,
+    )]
+    program_token_account: Account<'info, TokenAccount>,
+    token_mint: Account<'info, Mint>,
+    token_program: Program<'info, Token>,
+    #[account(mut)]
+    receipt_mint: Option<Account<'info, Mint>>,
 }
 
 #[derive(Accounts)]
 pub struct Safeguard<'info> {
     #[account(mut)]
     state: Account<'info, State>,
+    #[account(mut)]
     token_provider: Signer<'info>,
     /// CHECK: Safeguarding account is verified against the state account
     #[account(mut)]
     safeguarding_account: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReturnFromSafeguard<'info> {
+    #[account(mut)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    safeguarding_account: Signer<'info>,
+    #[account(
+        init,
+        payer = safeguarding_account,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeSafeguarded<'info> {
+    #[account(mut, seeds = [b"state"], bump)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    /// CHECK: Created and initialized by this instruction
+    #[account(mut, seeds = [b"stake"], bump)]
+    stake_account: UncheckedAccount<'info>,
+    /// CHECK: Verified against the state account
+    vote_account: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    /// CHECK: Stake config account required by the stake program
+    #[account(address = STAKE_CONFIG_ID)]
+    stake_config: UncheckedAccount<'info>,
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    stake_program: UncheckedAccount<'info>,
+    clock: Sysvar<'info, Clock>,
+    rent: Sysvar<'info, Rent>,
+    stake_history: Sysvar<'info, StakeHistory>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeactivateStake<'info> {
+    #[account(mut, seeds = [b"state"], bump)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    /// CHECK: Program-controlled stake account
+    #[account(mut, seeds = [b"stake"], bump)]
+    stake_account: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    stake_program: UncheckedAccount<'info>,
+    clock: Sysvar<'info, Clock>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    #[account(mut, seeds = [b"state"], bump)]
+    state: Account<'info, State>,
+    #[account(mut)]
+    token_provider: Signer<'info>,
+    /// CHECK: Program-controlled stake account
+    #[account(mut, seeds = [b"stake"], bump)]
+    stake_account: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = token_provider,
+        space = 8 + AuditEntry::INIT_SPACE,
+        seeds = [b"audit".as_ref(), &state.audit_entry_count.to_le_bytes()],
+        bump
+    )]
+    audit_entry: Account<'info, AuditEntry>,
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    stake_program: UncheckedAccount<'info>,
+    clock: Sysvar<'info, Clock>,
+    stake_history: Sysvar<'info, StakeHistory>,
+    system_program: Program<'info, System>,
 }
 
+// The stake config account is deprecated in the SDK but still required by `DelegateStake`
+pub const STAKE_CONFIG_ID: Pubkey = pubkey!("StakeConfig11111111111111111111111111111111");
+
 #[error_code]
 pub enum CustomError {
     #[msg("The contract has already been initialized.")]
@@ -586,4 +3479,96 @@ pub enum CustomError {
     ParticipantHasNotClaimed,
     #[msg("Participant has already been initialized")]
     ParticipantAlreadyInitialized,
+    #[msg("Participant is not on the priority allowlist.")]
+    NotAllowlisted,
+    #[msg("Priority allocation exceeded.")]
+    AllocationExceeded,
+    #[msg("Address is on the denylist.")]
+    AddressDenylisted,
+    #[msg("Fee must not exceed 10,000 basis points.")]
+    InvalidFee,
+    #[msg("Unauthorized fee recipient.")]
+    UnauthorizedFeeRecipient,
+    #[msg("Unauthorized beneficiary.")]
+    UnauthorizedBeneficiary,
+    #[msg("Beneficiary weights must sum to 10,000 basis points.")]
+    InvalidBeneficiarySplit,
+    #[msg("Invalid tranche.")]
+    InvalidTranche,
+    #[msg("Tranche has not been approved.")]
+    TrancheNotApproved,
+    #[msg("Tranche has already been released.")]
+    TrancheAlreadyReleased,
+    #[msg("Tranche is still locked.")]
+    TrancheLocked,
+    #[msg("Tokens/SOL have already been claimed.")]
+    AlreadyClaimed,
+    #[msg("Tranche vote is closed.")]
+    VoteClosed,
+    #[msg("Tranche vote is still open.")]
+    VoteStillOpen,
+    #[msg("Tranche vote has failed.")]
+    VoteFailed,
+    #[msg("Tranche vote has passed.")]
+    VotePassed,
+    #[msg("Escrow is being refunded.")]
+    EscrowRefunding,
+    #[msg("Escrow has not been released or refunded yet.")]
+    EscrowNotSettled,
+    #[msg("Invalid white paper hash or URI.")]
+    InvalidWhitePaper,
+    #[msg("A white paper must be published before activation.")]
+    WhitePaperMissing,
+    #[msg("Accepted terms do not match the current white paper.")]
+    TermsMismatch,
+    #[msg("Offer results have already been finalized.")]
+    AlreadyFinalized,
+    #[msg("Invalid price oracle.")]
+    InvalidPriceOracle,
+    #[msg("Oracle price is stale.")]
+    StalePrice,
+    #[msg("EUR cap exceeded.")]
+    EurCapExceeded,
+    #[msg("Amount exceeds the funds available for safeguarding.")]
+    SafeguardAmountExceeded,
+    #[msg("Safeguarding reserve must not exceed the threshold.")]
+    InvalidSafeguardThreshold,
+    #[msg("Staking has not been configured.")]
+    StakingNotConfigured,
+    #[msg("Invalid vote account.")]
+    InvalidVoteAccount,
+    #[msg("The stake account has already been created.")]
+    StakeAlreadyCreated,
+    #[msg("No active stake.")]
+    StakeNotActive,
+    #[msg("Stake must be withdrawn before distribution.")]
+    StakeStillActive,
+    #[msg("Rent payer does not match the participant account.")]
+    InvalidRentPayer,
+    #[msg("Batch accounts must be participant, wallet, rent payer, token account and denylist entry groups.")]
+    InvalidBatch,
+    #[msg("Token account is not owned by the claim destination.")]
+    InvalidClaimDestination,
+    #[msg("Position cannot be transferred during the priority round or once tranche votes have opened.")]
+    PositionLocked,
+    #[msg("Missing or invalid receipt accounts.")]
+    InvalidReceiptAccount,
+    #[msg("Receipt tokens require the position owner to participate.")]
+    ReceiptsRequireOwner,
+    #[msg("Participation intent signature is missing or does not match.")]
+    InvalidIntent,
+    #[msg("Participation intent has expired.")]
+    IntentExpired,
+    #[msg("Participation intent nonce has been used or is out of order.")]
+    InvalidIntentNonce,
+    #[msg("Tranche has already been approved.")]
+    TrancheAlreadyApproved,
+    #[msg("Tranche voting has not been configured.")]
+    VotingNotConfigured,
+    #[msg("Transferable receipts must be redeemed by the caller of each claim.")]
+    ReceiptsRedeemedByCaller,
+    #[msg("No automatic safeguarding left to record.")]
+    NoAutoSafeguardToRecord,
+    #[msg("Automatic safeguarding must be recorded in the audit log first.")]
+    AutoSafeguardNotRecorded,
 }
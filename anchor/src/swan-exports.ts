@@ -1,6 +1,7 @@
 // Here we export some useful types and functions for interacting with the Anchor program.
-import { AnchorProvider, Program } from '@coral-xyz/anchor'
+import { AnchorProvider, BN, IdlAccounts, Program } from '@coral-xyz/anchor'
 import { Cluster, PublicKey } from '@solana/web3.js'
+import { keccak_256 } from '@noble/hashes/sha3'
 import type { Swan } from '../target/types/swan'
 
 // Import the IDL JSON directly
@@ -19,4 +20,114 @@ export function getSwanProgramId(cluster: Cluster): PublicKey {
   return SWAN_PROGRAM_ID
 }
 
+export type SwanAuditEntry = IdlAccounts<Swan>['auditEntry']
+
+export function getSwanAuditEntryAddress(index: number | BN, programId: PublicKey = SWAN_PROGRAM_ID): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('audit'), new BN(index).toArrayLike(Buffer, 'le', 8)],
+    programId
+  )[0]
+}
+
+// Fetches the audit log entries of the sale in order
+export async function getSwanAuditLog(program: Program<Swan>, state: PublicKey): Promise<SwanAuditEntry[]> {
+  const stateAccount = await program.account.state.fetch(state)
+  const count = stateAccount.auditEntryCount.toNumber()
+  const addresses = Array.from({ length: count }, (_, index) => getSwanAuditEntryAddress(index, program.programId))
+  const entries = await program.account.auditEntry.fetchMultiple(addresses)
+  return entries.map((entry, index) => {
+    if (!entry) {
+      throw new Error(`Missing audit log entry ${index}`)
+    }
+    return entry
+  })
+}
+
+// Recomputes the hash chain; returns the index of the first tampered entry, or -1 if the log is intact
+export function verifySwanAuditLog(entries: SwanAuditEntry[]): number {
+  let prevHash = Buffer.alloc(32)
+  for (const [index, entry] of entries.entries()) {
+    const action = Object.keys(entry.action)
+    const hash = keccak_256(
+      Buffer.concat([
+        prevHash,
+        new BN(index).toArrayLike(Buffer, 'le', 8),
+        Buffer.from([AUDIT_ACTIONS.indexOf(action[0])]),
+        entry.actor.toBuffer(),
+        entry.amount.toArrayLike(Buffer, 'le', 8),
+        entry.slot.toArrayLike(Buffer, 'le', 8),
+        entry.timestamp.toTwos(64).toArrayLike(Buffer, 'le', 8),
+      ])
+    )
+    if (!prevHash.equals(Buffer.from(entry.prevHash)) || !Buffer.from(hash).equals(Buffer.from(entry.hash))) {
+      return index
+    }
+    prevHash = Buffer.from(hash)
+  }
+  return -1
+}
+
+// Message a participant signs off-chain to let a relayer call `participateWithIntent` for them
+export function getSwanIntentMessage(
+  state: PublicKey,
+  participant: PublicKey,
+  amount: BN,
+  maxAllocation: BN,
+  termsHash: number[] | Uint8Array,
+  nonce: BN,
+  expiry: BN,
+  programId: PublicKey = SWAN_PROGRAM_ID
+): Buffer {
+  return Buffer.concat([
+    Buffer.from('swan:participation-intent'),
+    programId.toBuffer(),
+    state.toBuffer(),
+    participant.toBuffer(),
+    amount.toArrayLike(Buffer, 'le', 8),
+    maxAllocation.toArrayLike(Buffer, 'le', 8),
+    Buffer.from(termsHash),
+    nonce.toArrayLike(Buffer, 'le', 8),
+    expiry.toArrayLike(Buffer, 'le', 8),
+  ])
+}
+
+// Leaf of the priority round allowlist: keccak(wallet || max allocation in lamports, 0 for no limit).
+// Pairs of nodes are hashed in ascending order to build the merkle root
+export function getSwanAllowlistLeaf(wallet: PublicKey, maxAllocation: BN): Buffer {
+  return Buffer.from(keccak_256(Buffer.concat([wallet.toBuffer(), maxAllocation.toArrayLike(Buffer, 'le', 8)])))
+}
+
+export type SwanState = IdlAccounts<Swan>['state']
+
+// Lamports the program keeps on the state account for refunds, as `refund_reserve` does: cancelled and
+// denylisted contributions, and contributions above the raise cap in oversubscription mode
+export function getSwanRefundReserve(state: SwanState): BN {
+  const allocated = state.totalContributed.sub(state.totalDenylisted)
+  const oversubscribed = state.oversubscriptionEnabled && allocated.gt(state.raiseCap)
+    ? allocated.sub(state.raiseCap)
+    : new BN(0)
+  return state.totalCancelled.add(state.totalDenylisted).add(oversubscribed)
+}
+
+// Variant order of the on-chain AuditAction enum
+const AUDIT_ACTIONS = [
+  'deposit',
+  'configure',
+  'publishWhitePaper',
+  'activate',
+  'addToDenylist',
+  'removeFromDenylist',
+  'allowDenylistedRefund',
+  'safeguard',
+  'distribute',
+  'approveTranche',
+  'releaseTranche',
+  'end',
+  'returnFromSafeguard',
+  'stakeSafeguarded',
+  'deactivateStake',
+  'withdrawStake',
+  'autoSafeguard',
+]
+
 export type { Swan }
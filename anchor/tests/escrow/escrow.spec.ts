@@ -0,0 +1,381 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+} from "@solana/web3.js";
+import { Swan } from "../../target/types/swan";
+import { getSwanIntentMessage } from "../../src/swan-exports";
+import { createMint, getAccount, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { createHash } from "crypto";
+
+const VOTE_DURATION = 10; // seconds
+
+// Escrowed raise released in four tranches, each approved by the custodian and put to a
+// contributor vote: alice contributes 3.33 SOL and bob 1 SOL through a relayed intent.
+// Releases are split between the beneficiary, a foundation and a treasury.
+// Runs on its own validator through tests/escrow/Test.toml, run all suites with anchor test
+describe("swan escrow", () => {
+  let swanProgram: Program<Swan>;
+  let connection: anchor.web3.Connection;
+  let tokenMint: PublicKey;
+  let statePda: PublicKey;
+  const tokenProvider = Keypair.generate();
+  const beneficiary = Keypair.generate();
+  const foundation = Keypair.generate();
+  const treasury = Keypair.generate();
+  const safeGuard = Keypair.generate();
+  const platform = Keypair.generate();
+  const custodian = Keypair.generate();
+  const alice = Keypair.generate();
+  const bob = Keypair.generate();
+  const whitePaperHash = Array.from(createHash("sha256").update("white paper v1").digest());
+
+  const participantAddress = (wallet: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("participant"), wallet.toBuffer()],
+    swanProgram.programId
+  )[0];
+
+  const airdrop = async (wallet: PublicKey, sol: number) => {
+    const signature = await connection.requestAirdrop(wallet, sol * LAMPORTS_PER_SOL);
+    await connection.confirmTransaction(signature);
+  };
+
+  const participate = (wallet: Keypair, lamports: number) =>
+    swanProgram.methods.participate(
+      new anchor.BN(lamports),
+      new anchor.BN(0), // no priority allocation
+      [],
+      whitePaperHash, // accepted terms
+    ).accountsPartial({
+      state: statePda,
+      participant: wallet.publicKey,
+      payer: wallet.publicKey,
+    }).signers([wallet]).rpc();
+
+  // Bob's intent to contribute 1 SOL, signed off-chain and submitted by the token provider
+  const relayIntent = () => {
+    const amount = new anchor.BN(LAMPORTS_PER_SOL);
+    const nonce = new anchor.BN(0);
+    const expiry = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+    const message = getSwanIntentMessage(
+      statePda, bob.publicKey, amount, new anchor.BN(0), whitePaperHash, nonce, expiry, swanProgram.programId
+    );
+    return swanProgram.methods.participateWithIntent(
+      amount,
+      new anchor.BN(0),
+      [],
+      whitePaperHash,
+      nonce,
+      expiry,
+    ).accountsPartial({
+      state: statePda,
+      relayer: tokenProvider.publicKey,
+      participant: bob.publicKey,
+    }).preInstructions([
+      Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: bob.secretKey,
+        message,
+      }),
+    ]).signers([tokenProvider]).rpc();
+  };
+
+  const claim = async (wallet: Keypair) => {
+    const tokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, wallet.publicKey);
+    const [, programTokenAccountBump] = PublicKey.findProgramAddressSync(
+      [tokenMint.toBuffer()],
+      swanProgram.programId
+    );
+    await swanProgram.methods.claim(programTokenAccountBump).accountsPartial({
+      participantAccount: participantAddress(wallet.publicKey),
+      state: statePda,
+      participant: wallet.publicKey,
+      rentPayer: wallet.publicKey,
+      participantTokenAccount: tokenAccount.address,
+      tokenMint: tokenMint,
+    }).signers([wallet]).rpc();
+    return tokenAccount.address;
+  };
+
+  const approve = (index: number) =>
+    swanProgram.methods.approveTranche(index).accounts({
+      state: statePda,
+      custodian: custodian.publicKey,
+    }).signers([custodian]).rpc();
+
+  const vote = (wallet: Keypair, index: number, inFavour: boolean) =>
+    swanProgram.methods.voteTranche(index, inFavour).accounts({
+      state: statePda,
+      participant: wallet.publicKey,
+    }).signers([wallet]).rpc();
+
+  const finalize = () =>
+    swanProgram.methods.finalize().accounts({
+      state: statePda,
+      payer: tokenProvider.publicKey,
+      tokenMint: tokenMint,
+    }).signers([tokenProvider]).rpc();
+
+  const release = (index: number) =>
+    swanProgram.methods.releaseTranche(index).accounts({
+      state: statePda,
+      payer: tokenProvider.publicKey,
+      beneficiary: beneficiary.publicKey,
+    }).remainingAccounts([
+      { pubkey: foundation.publicKey, isWritable: true, isSigner: false },
+      { pubkey: treasury.publicKey, isWritable: true, isSigner: false },
+    ]).signers([tokenProvider]).rpc();
+
+  beforeAll(async () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    swanProgram = anchor.workspace.Swan as Program<Swan>;
+    connection = anchor.getProvider().connection;
+    [statePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state")],
+      swanProgram.programId
+    );
+  }, 50000 * 60);
+
+  it("should open an offer escrowed in four voted tranches", async () => {
+    await airdrop(tokenProvider.publicKey, 10);
+    await airdrop(custodian.publicKey, 1);
+
+    await swanProgram.methods.init(
+      tokenProvider.publicKey,
+      beneficiary.publicKey,
+      safeGuard.publicKey,
+      0, // no platform fee
+      platform.publicKey,
+    ).accounts({
+      signer: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    tokenMint = await createMint(connection, tokenProvider, tokenProvider.publicKey, tokenProvider.publicKey, 9);
+    const providerAta = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, tokenProvider.publicKey);
+    await mintTo(connection, tokenProvider, tokenMint, providerAta.address, tokenProvider, 1_000_000_000_000_000_000);
+    await swanProgram.methods.deposit(
+      new anchor.BN("1000000000000000000")
+    ).accounts({
+      state: statePda,
+      tokenAuthority: tokenProvider.publicKey,
+      fromTokenAccount: providerAta.address,
+      tokenMint: tokenMint,
+    }).signers([tokenProvider]).rpc();
+
+    const version = Buffer.alloc(2);
+    version.writeUInt16LE(1);
+    const [whitePaperPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("white_paper"), version],
+      swanProgram.programId
+    );
+    await swanProgram.methods.publishWhitePaper(
+      whitePaperHash,
+      "https://example.com/white-paper-v1.pdf"
+    ).accountsPartial({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+      whitePaper: whitePaperPda,
+    }).signers([tokenProvider]).rpc();
+
+    const tranche = (bps: number) => ({
+      bps,
+      unlockTime: new anchor.BN(0),
+      approved: false,
+      released: false,
+      releasedLamports: new anchor.BN(0),
+      voteEnd: new anchor.BN(0),
+      votesFor: new anchor.BN(0),
+      votesAgainst: new anchor.BN(0),
+    });
+    await swanProgram.methods.configureTranches(
+      custodian.publicKey,
+      [tranche(4000), tranche(2000), tranche(2000), tranche(2000)],
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.configureTrancheVote(
+      new anchor.BN(VOTE_DURATION)
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    // the first entry receives the rounding remainder of each release
+    await swanProgram.methods.configureBeneficiaries([
+      { recipient: beneficiary.publicKey, bps: 3334, paidLamports: new anchor.BN(0) },
+      { recipient: foundation.publicKey, bps: 3333, paidLamports: new anchor.BN(0) },
+      { recipient: treasury.publicKey, bps: 3333, paidLamports: new anchor.BN(0) },
+    ]).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.activate(
+      new anchor.BN(10000000000)
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.tranches.length).toEqual(4);
+    expect(stateAccount.trancheVoteDuration.toNumber()).toEqual(VOTE_DURATION);
+    expect(stateAccount.beneficiaries.length).toEqual(3);
+  }, 50000 * 60);
+
+  it("should create the participant account from a relayed intent", async () => {
+    await airdrop(bob.publicKey, 1);
+    expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(bob.publicKey))).toBeNull();
+
+    await relayIntent();
+
+    const [intentNonce] = PublicKey.findProgramAddressSync(
+      [Buffer.from("intent_nonce"), bob.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    expect((await swanProgram.account.intentNonce.fetch(intentNonce)).next.toNumber()).toEqual(1);
+    const position = await swanProgram.account.participantAccount.fetch(participantAddress(bob.publicKey));
+    expect(position.participant.toBase58()).toEqual(bob.publicKey.toBase58());
+    expect(position.rentPayer.toBase58()).toEqual(tokenProvider.publicKey.toBase58());
+    expect(position.amount.toString()).toEqual(LAMPORTS_PER_SOL.toString());
+    expect(position.acceptedTermsVersion).toEqual(1);
+  }, 50000 * 60);
+
+  it("should execute a relayed intent only once, even after the position moved", async () => {
+    const bobNewWallet = Keypair.generate();
+
+    // moving the position away and back recreates the participant account
+    await swanProgram.methods.transferPosition(bobNewWallet.publicKey).accounts({
+      state: statePda,
+      participant: bob.publicKey,
+      payer: bob.publicKey,
+      rentPayer: tokenProvider.publicKey,
+    }).signers([bob]).rpc();
+    await swanProgram.methods.transferPosition(bob.publicKey).accounts({
+      state: statePda,
+      participant: bobNewWallet.publicKey,
+      payer: bob.publicKey,
+      rentPayer: bob.publicKey,
+    }).signers([bobNewWallet, bob]).rpc();
+
+    // the nonce is kept per wallet, so the intent cannot be replayed
+    await expect(relayIntent()).rejects.toThrow(/InvalidIntentNonce/);
+    const position = await swanProgram.account.participantAccount.fetch(participantAddress(bob.publicKey));
+    expect(position.amount.toString()).toEqual(LAMPORTS_PER_SOL.toString());
+  }, 50000 * 60);
+
+  it("should escrow the raise at distribution", async () => {
+    await airdrop(alice.publicKey, 4);
+    await participate(alice, 3_333_333_333);
+
+    await swanProgram.methods.end().accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await swanProgram.methods.distribute().accounts({
+      state: statePda,
+      payer: tokenProvider.publicKey,
+      beneficiary: beneficiary.publicKey,
+      feeRecipient: platform.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.escrowedLamports.toString()).toEqual("4333333333");
+    expect(await connection.getBalance(beneficiary.publicKey)).toEqual(0);
+    expect(await connection.getBalance(foundation.publicKey)).toEqual(0);
+
+    // participants keep their accounts after claiming, to vote and settle the escrow
+    expect((await getAccount(connection, await claim(alice))).amount.toString()).toEqual("769230769213017213");
+    expect((await getAccount(connection, await claim(bob))).amount.toString()).toEqual("230769230786982786");
+    expect((await swanProgram.account.participantAccount.fetch(participantAddress(alice.publicKey))).claimed).toEqual(1);
+  }, 50000 * 60);
+
+  it("should approve each tranche once and open its vote", async () => {
+    await approve(0);
+    await approve(1);
+    await approve(2);
+
+    // a second approval would reopen the vote
+    await expect(approve(1)).rejects.toThrow(/TrancheAlreadyApproved/);
+
+    await vote(alice, 0, true);
+    await vote(bob, 2, false);
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.tranches[0].votesFor.toString()).toEqual("3333333333");
+    expect(stateAccount.tranches[2].votesAgainst.toString()).toEqual(LAMPORTS_PER_SOL.toString());
+    await expect(release(0)).rejects.toThrow(/VoteStillOpen/);
+  }, 50000 * 60);
+
+  it("should release a tranche once its vote has passed", async () => {
+    await delay((VOTE_DURATION + 2) * 1000);
+
+    await release(0);
+
+    // 40% of the 4.33 SOL escrow, 33.33% each to the foundation and the treasury
+    // and the remainder to the beneficiary
+    expect(await connection.getBalance(beneficiary.publicKey)).toEqual(577_893_335);
+    expect(await connection.getBalance(foundation.publicKey)).toEqual(577_719_999);
+    expect(await connection.getBalance(treasury.publicKey)).toEqual(577_719_999);
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.tranches[0].released).toEqual(true);
+    expect(stateAccount.releasedLamports.toString()).toEqual("1733333333");
+    expect(stateAccount.beneficiaries.map((share) => share.paidLamports.toString()))
+      .toEqual(["577893335", "577719999", "577719999"]);
+
+    // the results are not final while part of the escrow is still held
+    await expect(finalize()).rejects.toThrow(/EscrowNotSettled/);
+  }, 50000 * 60);
+
+  it("should not release a tranche nobody voted on", async () => {
+    await expect(release(1)).rejects.toThrow(/VoteFailed/);
+  });
+
+  it("should refund the remaining escrow pro-rata after a failed vote", async () => {
+    await expect(release(2)).rejects.toThrow(/VoteFailed/);
+    await swanProgram.methods.failTranche(2).accounts({
+      state: statePda,
+    }).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.escrowRefunding).toEqual(true);
+    expect(stateAccount.escrowRefundLamports.toString()).toEqual("2600000000");
+
+    // no tranche can be approved once the escrow is refunding
+    await expect(approve(3)).rejects.toThrow(/EscrowRefunding/);
+
+    // the 2.6 SOL left, pro-rata to alice's 3.33 SOL and bob's 1 SOL
+    for (const [wallet, refund] of [[alice, 1_999_999_999], [bob, 600_000_000]] as const) {
+      const balanceBefore = await connection.getBalance(wallet.publicKey);
+      const participantAccountRent = await connection.getBalance(participantAddress(wallet.publicKey));
+
+      await swanProgram.methods.settleEscrow().accountsPartial({
+        state: statePda,
+        participant: wallet.publicKey,
+        rentPayer: wallet.publicKey,
+      }).signers([wallet]).rpc();
+
+      expect(await connection.getBalance(wallet.publicKey)).toEqual(balanceBefore + refund + participantAccountRent);
+      expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(wallet.publicKey))).toBeNull();
+    }
+  }, 50000 * 60);
+
+  it("should finalize the results once the escrow is refunding", async () => {
+    await finalize();
+
+    const [resultsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("results")],
+      swanProgram.programId
+    );
+    const results = await swanProgram.account.offerResults.fetch(resultsPda);
+    expect(results.grossRaised.toString()).toEqual("4333333333");
+    expect(results.totalRefunded.toString()).toEqual("2600000000");
+    expect(results.netToBeneficiary.toString()).toEqual("1733333333");
+  }, 50000 * 60);
+});
+
+const delay = (ms: number) => new Promise((res) => setTimeout(res, ms));
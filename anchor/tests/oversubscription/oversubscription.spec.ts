@@ -0,0 +1,341 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+} from "@solana/web3.js";
+import { Swan } from "../../target/types/swan";
+import { getSwanAuditLog, verifySwanAuditLog } from "../../src/swan-exports";
+import { createMint, getAccount, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_PROGRAM_ID, transfer } from "@solana/spl-token";
+import { createHash } from "crypto";
+
+// Oversubscribed offer with a platform fee, receipt tokens and automatic safeguarding: 22 wallets
+// contribute 250 SOL each against the 5,000 SOL raise cap and one more wallet cancels its contribution.
+// Runs on its own validator through tests/oversubscription/Test.toml, run all suites with anchor test
+describe("swan oversubscription", () => {
+  let swanProgram: Program<Swan>;
+  let connection: anchor.web3.Connection;
+  let tokenMint: PublicKey;
+  let statePda: PublicKey;
+  let receiptMint: PublicKey;
+  const tokenProvider = Keypair.generate();
+  const beneficiary = Keypair.generate();
+  const safeGuard = Keypair.generate();
+  const platform = Keypair.generate();
+  const canceller = Keypair.generate();
+  const wallets = Array.from({ length: 22 }, () => Keypair.generate());
+  const whitePaperHash = Array.from(createHash("sha256").update("white paper v1").digest());
+
+  const participantAddress = (wallet: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("participant"), wallet.toBuffer()],
+    swanProgram.programId
+  )[0];
+
+  const airdrop = async (wallet: PublicKey, sol: number) => {
+    const signature = await connection.requestAirdrop(wallet, sol * LAMPORTS_PER_SOL);
+    await connection.confirmTransaction(signature);
+  };
+
+  const receiptAccounts = async (wallet: PublicKey) => ({
+    receiptMint,
+    receiptTokenAccount: (await getOrCreateAssociatedTokenAccount(connection, tokenProvider, receiptMint, wallet)).address,
+    tokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  const participate = async (wallet: Keypair, sol: number) =>
+    swanProgram.methods.participate(
+      new anchor.BN(sol * LAMPORTS_PER_SOL),
+      new anchor.BN(0), // no priority allocation
+      [],
+      whitePaperHash, // accepted terms
+    ).accountsPartial({
+      state: statePda,
+      participant: wallet.publicKey,
+      payer: wallet.publicKey,
+      safeguardingAccount: safeGuard.publicKey,
+      ...(await receiptAccounts(wallet.publicKey)),
+    }).signers([wallet]).rpc();
+
+  const claim = async (wallet: Keypair) => {
+    const tokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, wallet.publicKey);
+    const [, programTokenAccountBump] = PublicKey.findProgramAddressSync(
+      [tokenMint.toBuffer()],
+      swanProgram.programId
+    );
+    await swanProgram.methods.claim(programTokenAccountBump).accountsPartial({
+      participantAccount: participantAddress(wallet.publicKey),
+      state: statePda,
+      participant: wallet.publicKey,
+      rentPayer: wallet.publicKey,
+      participantTokenAccount: tokenAccount.address,
+      tokenMint: tokenMint,
+      ...(await receiptAccounts(wallet.publicKey)),
+    }).signers([wallet]).rpc();
+    return tokenAccount.address;
+  };
+
+  beforeAll(async () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    swanProgram = anchor.workspace.Swan as Program<Swan>;
+    connection = anchor.getProvider().connection;
+    [statePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state")],
+      swanProgram.programId
+    );
+    [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint")],
+      swanProgram.programId
+    );
+  }, 50000 * 60);
+
+  it("should open an oversubscribed offer with a 1% platform fee, receipt tokens and automatic safeguarding", async () => {
+    await airdrop(tokenProvider.publicKey, 10);
+
+    await swanProgram.methods.init(
+      tokenProvider.publicKey,
+      beneficiary.publicKey,
+      safeGuard.publicKey,
+      100, // 1% platform fee
+      platform.publicKey,
+    ).accounts({
+      signer: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    tokenMint = await createMint(connection, tokenProvider, tokenProvider.publicKey, tokenProvider.publicKey, 9);
+    const providerAta = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, tokenProvider.publicKey);
+    await mintTo(connection, tokenProvider, tokenMint, providerAta.address, tokenProvider, 1_000_000_000_000_000_000);
+    await swanProgram.methods.deposit(
+      new anchor.BN("1000000000000000000")
+    ).accounts({
+      state: statePda,
+      tokenAuthority: tokenProvider.publicKey,
+      fromTokenAccount: providerAta.address,
+      tokenMint: tokenMint,
+    }).signers([tokenProvider]).rpc();
+
+    const version = Buffer.alloc(2);
+    version.writeUInt16LE(1);
+    const [whitePaperPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("white_paper"), version],
+      swanProgram.programId
+    );
+    await swanProgram.methods.publishWhitePaper(
+      whitePaperHash,
+      "https://example.com/white-paper-v1.pdf"
+    ).accountsPartial({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+      whitePaper: whitePaperPda,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.configureOversubscription(true).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    // above 500 SOL held, everything but 100 SOL and the refund reserve moves to the safeguarding account
+    await swanProgram.methods.configureAutoSafeguard(
+      new anchor.BN(500 * LAMPORTS_PER_SOL),
+      new anchor.BN(100 * LAMPORTS_PER_SOL),
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.configureReceipts(false).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.activate(
+      new anchor.BN(10000000000)
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.oversubscriptionEnabled).toEqual(true);
+    expect(stateAccount.feeBps).toEqual(100);
+    expect(stateAccount.receiptMint.toBase58()).toEqual(receiptMint.toBase58());
+  }, 50000 * 60);
+
+  it("should accept 5,500 SOL against the 5,000 SOL raise cap", async () => {
+    await airdrop(canceller.publicKey, 51);
+    await participate(canceller, 50);
+    await swanProgram.methods.cancel().accountsPartial({
+      state: statePda,
+      participant: canceller.publicKey,
+      participantAccount: participantAddress(canceller.publicKey),
+      ...(await receiptAccounts(canceller.publicKey)),
+    }).signers([canceller]).rpc();
+
+    for (const wallet of wallets) {
+      await airdrop(wallet.publicKey, 251);
+      await participate(wallet, 250);
+    }
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.totalContributed.toString()).toEqual((5500 * LAMPORTS_PER_SOL).toString());
+    expect(stateAccount.totalCancelled.toString()).toEqual((50 * LAMPORTS_PER_SOL).toString());
+    expect(stateAccount.activeEarlyInvestorCount.toNumber()).toEqual(22);
+    expect(stateAccount.largeInvestorCount.toNumber()).toEqual(22);
+  }, 50000 * 60);
+
+  it("should safeguard automatically while keeping the refund reserve", async () => {
+    // every second wallet crosses the 500 SOL threshold, and the last one only moves 50 SOL as the
+    // 50 SOL cancelled and the 500 SOL above the raise cap stay on the contract for refunds
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.totalSafeguarded.toString()).toEqual((5000 * LAMPORTS_PER_SOL).toString());
+    expect(await connection.getBalance(safeGuard.publicKey)).toEqual(5000 * LAMPORTS_PER_SOL);
+    const rentExempt = await connection.getMinimumBalanceForRentExemption(
+      (await connection.getAccountInfo(statePda))!.data.length
+    );
+    expect(await connection.getBalance(statePda)).toEqual(rentExempt + 550 * LAMPORTS_PER_SOL);
+
+    // participations do not write to the audit log, the movements wait for the token provider to record them
+    expect(stateAccount.unrecordedAutoSafeguarded.toString()).toEqual((5000 * LAMPORTS_PER_SOL).toString());
+    const entries = await getSwanAuditLog(swanProgram, statePda);
+    expect(entries.filter((entry) => "autoSafeguard" in entry.action).length).toEqual(0);
+
+    await swanProgram.methods.returnFromSafeguard(
+      new anchor.BN(5000 * LAMPORTS_PER_SOL)
+    ).accounts({
+      state: statePda,
+      safeguardingAccount: safeGuard.publicKey,
+    }).signers([safeGuard]).rpc();
+  }, 50000 * 60);
+
+  it("should take the fee from the payout left after the refund reserve", async () => {
+    await swanProgram.methods.end().accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const distribute = () =>
+      swanProgram.methods.distribute().accounts({
+        state: statePda,
+        payer: tokenProvider.publicKey,
+        beneficiary: beneficiary.publicKey,
+        feeRecipient: platform.publicKey,
+      }).signers([tokenProvider]).rpc();
+
+    // the automatic movements must be in the audit log before the funds are paid out
+    await expect(distribute()).rejects.toThrow(/AutoSafeguardNotRecorded/);
+    await swanProgram.methods.recordAutoSafeguard().accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const entries = await getSwanAuditLog(swanProgram, statePda);
+    const movements = entries.filter((entry) => "autoSafeguard" in entry.action);
+    expect(movements.length).toEqual(1);
+    expect(movements[0].amount.toString()).toEqual((5000 * LAMPORTS_PER_SOL).toString());
+    expect(movements[0].actor.toBase58()).toEqual(tokenProvider.publicKey.toBase58());
+    expect(verifySwanAuditLog(entries)).toEqual(-1);
+
+    await distribute();
+
+    // 50 SOL cancelled and 500 SOL oversubscribed stay on the contract, 1% of the other 5,000 SOL is the fee
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.totalOversubscribed.toString()).toEqual((500 * LAMPORTS_PER_SOL).toString());
+    expect(stateAccount.feeLamports.toString()).toEqual((50 * LAMPORTS_PER_SOL).toString());
+    expect(stateAccount.recipientLamports.toString()).toEqual((4950 * LAMPORTS_PER_SOL).toString());
+    expect(await connection.getBalance(beneficiary.publicKey)).toEqual(4950 * LAMPORTS_PER_SOL);
+    expect(await connection.getBalance(platform.publicKey)).toEqual(50 * LAMPORTS_PER_SOL);
+
+    const rentExempt = await connection.getMinimumBalanceForRentExemption(
+      (await connection.getAccountInfo(statePda))!.data.length
+    );
+    expect(await connection.getBalance(statePda)).toEqual(rentExempt + 550 * LAMPORTS_PER_SOL);
+  }, 50000 * 60);
+
+  it("should pay the token share and the oversubscription refund of two participants", async () => {
+    for (const wallet of wallets.slice(0, 2)) {
+      const balanceBefore = await connection.getBalance(wallet.publicKey);
+      const participantAccountRent = await connection.getBalance(participantAddress(wallet.publicKey));
+
+      const tokenAccount = await claim(wallet);
+
+      // (1e18 - 44,000 bonus tokens) * 250 / 5,500 plus the early and large investor bonuses
+      expect((await getAccount(connection, tokenAccount)).amount.toString()).toEqual("45454545454545454");
+      // 250 / 5,500 of the 500 oversubscribed SOL, and the account rent
+      expect(await connection.getBalance(wallet.publicKey)).toEqual(
+        balanceBefore + 22_727_272_727 + participantAccountRent
+      );
+      expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(wallet.publicKey))).toBeNull();
+    }
+  }, 50000 * 60);
+
+  it("should keep receipts with the position owner until they are redeemed on claim", async () => {
+    const holder = wallets[2];
+    const { receiptTokenAccount } = await receiptAccounts(holder.publicKey);
+    const otherReceiptAccount = (await receiptAccounts(wallets[3].publicKey)).receiptTokenAccount;
+    expect((await getAccount(connection, receiptTokenAccount)).amount.toString()).toEqual((250 * LAMPORTS_PER_SOL).toString());
+
+    // claims pay the position owner, so the receipts cannot be handed to anyone else
+    await expect(
+      transfer(connection, tokenProvider, receiptTokenAccount, otherReceiptAccount, holder, 1)
+    ).rejects.toThrow();
+    expect((await getAccount(connection, receiptTokenAccount)).isFrozen).toEqual(true);
+
+    const tokenAccount = await claim(holder);
+
+    expect((await getAccount(connection, tokenAccount)).amount.toString()).toEqual("45454545454545454");
+    expect((await getAccount(connection, receiptTokenAccount)).amount.toString()).toEqual("0");
+  }, 50000 * 60);
+
+  it("should settle participants in a batch claim, closing their accounts", async () => {
+    const batch = wallets.slice(3, 5);
+    const groups = [];
+    const balancesBefore = [];
+    const participantAccountRents = [];
+    for (const wallet of batch) {
+      const tokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, wallet.publicKey);
+      const [denylistEntry] = PublicKey.findProgramAddressSync(
+        [Buffer.from("denylist"), wallet.publicKey.toBuffer()],
+        swanProgram.programId
+      );
+      const { receiptTokenAccount } = await receiptAccounts(wallet.publicKey);
+      groups.push(...[
+        participantAddress(wallet.publicKey),
+        wallet.publicKey,
+        wallet.publicKey, // rent payer
+        tokenAccount.address,
+        denylistEntry,
+        receiptTokenAccount,
+      ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })));
+      balancesBefore.push(await connection.getBalance(wallet.publicKey));
+      participantAccountRents.push(await connection.getBalance(participantAddress(wallet.publicKey)));
+    }
+
+    // the caller is the provider wallet, none of the participants sign
+    await swanProgram.methods.batchClaim().accounts({
+      state: statePda,
+      tokenMint: tokenMint,
+      receiptMint: receiptMint,
+    }).remainingAccounts(groups).rpc();
+
+    for (const [i, wallet] of batch.entries()) {
+      const tokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, wallet.publicKey);
+      expect(tokenAccount.amount.toString()).toEqual("45454545454545454");
+      expect(await connection.getBalance(wallet.publicKey)).toEqual(
+        balancesBefore[i] + 22_727_272_727 + participantAccountRents[i]
+      );
+      expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(wallet.publicKey))).toBeNull();
+    }
+  }, 50000 * 60);
+
+  it("should refund the cancelled contribution from the reserve", async () => {
+    const balanceBefore = await connection.getBalance(canceller.publicKey);
+    const participantAccountRent = await connection.getBalance(participantAddress(canceller.publicKey));
+
+    const tokenAccount = await claim(canceller);
+
+    expect((await getAccount(connection, tokenAccount)).amount.toString()).toEqual("0");
+    expect(await connection.getBalance(canceller.publicKey)).toEqual(
+      balanceBefore + 50 * LAMPORTS_PER_SOL + participantAccountRent
+    );
+  }, 50000 * 60);
+});
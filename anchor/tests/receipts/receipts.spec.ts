@@ -0,0 +1,225 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+} from "@solana/web3.js";
+import { Swan } from "../../target/types/swan";
+import { createMint, getAccount, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_PROGRAM_ID, transfer } from "@solana/spl-token";
+import { createHash } from "crypto";
+
+// Offer with transferable receipt tokens: alice contributes 2 SOL and sells her receipts to carol,
+// her claim delegate, who redeems them for alice's tokens; bob contributes 1 SOL and keeps his receipts.
+// Runs on its own validator through tests/receipts/Test.toml, run all suites with anchor test
+describe("swan transferable receipts", () => {
+  let swanProgram: Program<Swan>;
+  let connection: anchor.web3.Connection;
+  let tokenMint: PublicKey;
+  let statePda: PublicKey;
+  let receiptMint: PublicKey;
+  const tokenProvider = Keypair.generate();
+  const beneficiary = Keypair.generate();
+  const safeGuard = Keypair.generate();
+  const platform = Keypair.generate();
+  const alice = Keypair.generate();
+  const bob = Keypair.generate();
+  const carol = Keypair.generate();
+  const whitePaperHash = Array.from(createHash("sha256").update("white paper v1").digest());
+
+  const participantAddress = (wallet: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("participant"), wallet.toBuffer()],
+    swanProgram.programId
+  )[0];
+
+  const airdrop = async (wallet: PublicKey, sol: number) => {
+    const signature = await connection.requestAirdrop(wallet, sol * LAMPORTS_PER_SOL);
+    await connection.confirmTransaction(signature);
+  };
+
+  const receiptAccounts = async (wallet: PublicKey) => ({
+    receiptMint,
+    receiptTokenAccount: (await getOrCreateAssociatedTokenAccount(connection, tokenProvider, receiptMint, wallet)).address,
+    tokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  const participate = async (wallet: Keypair, lamports: number) =>
+    swanProgram.methods.participate(
+      new anchor.BN(lamports),
+      new anchor.BN(0), // no priority allocation
+      [],
+      whitePaperHash, // accepted terms
+    ).accountsPartial({
+      state: statePda,
+      participant: wallet.publicKey,
+      payer: wallet.publicKey,
+      ...(await receiptAccounts(wallet.publicKey)),
+    }).signers([wallet]).rpc();
+
+  const claim = async (wallet: Keypair) => {
+    const tokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, wallet.publicKey);
+    const [, programTokenAccountBump] = PublicKey.findProgramAddressSync(
+      [tokenMint.toBuffer()],
+      swanProgram.programId
+    );
+    await swanProgram.methods.claim(programTokenAccountBump).accountsPartial({
+      participantAccount: participantAddress(wallet.publicKey),
+      state: statePda,
+      participant: wallet.publicKey,
+      rentPayer: wallet.publicKey,
+      participantTokenAccount: tokenAccount.address,
+      tokenMint: tokenMint,
+      ...(await receiptAccounts(wallet.publicKey)),
+    }).signers([wallet]).rpc();
+    return tokenAccount.address;
+  };
+
+  beforeAll(async () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    swanProgram = anchor.workspace.Swan as Program<Swan>;
+    connection = anchor.getProvider().connection;
+    [statePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state")],
+      swanProgram.programId
+    );
+    [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint")],
+      swanProgram.programId
+    );
+  }, 50000 * 60);
+
+  it("should open an offer with transferable receipt tokens", async () => {
+    await airdrop(tokenProvider.publicKey, 10);
+
+    await swanProgram.methods.init(
+      tokenProvider.publicKey,
+      beneficiary.publicKey,
+      safeGuard.publicKey,
+      0, // no platform fee
+      platform.publicKey,
+    ).accounts({
+      signer: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    tokenMint = await createMint(connection, tokenProvider, tokenProvider.publicKey, tokenProvider.publicKey, 9);
+    const providerAta = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, tokenProvider.publicKey);
+    await mintTo(connection, tokenProvider, tokenMint, providerAta.address, tokenProvider, 1_000_000_000_000_000_000);
+    await swanProgram.methods.deposit(
+      new anchor.BN("1000000000000000000")
+    ).accounts({
+      state: statePda,
+      tokenAuthority: tokenProvider.publicKey,
+      fromTokenAccount: providerAta.address,
+      tokenMint: tokenMint,
+    }).signers([tokenProvider]).rpc();
+
+    const version = Buffer.alloc(2);
+    version.writeUInt16LE(1);
+    const [whitePaperPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("white_paper"), version],
+      swanProgram.programId
+    );
+    await swanProgram.methods.publishWhitePaper(
+      whitePaperHash,
+      "https://example.com/white-paper-v1.pdf"
+    ).accountsPartial({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+      whitePaper: whitePaperPda,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.configureReceipts(true).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.activate(
+      new anchor.BN(10000000000)
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.receiptMint.toBase58()).toEqual(receiptMint.toBase58());
+    expect(stateAccount.receiptsTransferable).toEqual(true);
+  }, 50000 * 60);
+
+  it("should let alice hand her receipts to carol", async () => {
+    await airdrop(alice.publicKey, 3);
+    await airdrop(bob.publicKey, 2);
+    await airdrop(carol.publicKey, 1);
+    await participate(alice, 2 * LAMPORTS_PER_SOL);
+    await participate(bob, LAMPORTS_PER_SOL);
+
+    const aliceReceipts = (await receiptAccounts(alice.publicKey)).receiptTokenAccount;
+    const carolReceipts = (await receiptAccounts(carol.publicKey)).receiptTokenAccount;
+    expect((await getAccount(connection, aliceReceipts)).isFrozen).toEqual(false);
+
+    // carol buys the receipts and the right to claim alice's tokens to her own wallet
+    await swanProgram.methods.setClaimOptions(
+      carol.publicKey,
+      carol.publicKey,
+    ).accounts({
+      participant: alice.publicKey,
+    }).signers([alice]).rpc();
+    await transfer(connection, tokenProvider, aliceReceipts, carolReceipts, alice, 2 * LAMPORTS_PER_SOL);
+
+    expect((await getAccount(connection, aliceReceipts)).amount.toString()).toEqual("0");
+    expect((await getAccount(connection, carolReceipts)).amount.toString()).toEqual((2 * LAMPORTS_PER_SOL).toString());
+
+    // the receipts are gone, so alice can no longer cancel the position she sold
+    await expect(
+      swanProgram.methods.cancel().accountsPartial({
+        state: statePda,
+        participant: alice.publicKey,
+        participantAccount: participantAddress(alice.publicKey),
+        ...(await receiptAccounts(alice.publicKey)),
+      }).signers([alice]).rpc()
+    ).rejects.toThrow();
+  }, 50000 * 60);
+
+  it("should redeem the receipts held by the caller of each claim", async () => {
+    await swanProgram.methods.end().accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await swanProgram.methods.distribute().accounts({
+      state: statePda,
+      payer: tokenProvider.publicKey,
+      beneficiary: beneficiary.publicKey,
+      feeRecipient: platform.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    // nobody signs a batch claim, so nobody could redeem the receipts
+    await expect(
+      swanProgram.methods.batchClaim().accounts({
+        state: statePda,
+        tokenMint: tokenMint,
+        receiptMint: receiptMint,
+      }).rpc()
+    ).rejects.toThrow(/ReceiptsRedeemedByCaller/);
+
+    // alice holds no receipts to redeem
+    await expect(claim(alice)).rejects.toThrow();
+
+    const carolTokenAccount = await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, carol.publicKey);
+    await swanProgram.methods.delegatedClaim().accountsPartial({
+      state: statePda,
+      delegate: carol.publicKey,
+      participant: alice.publicKey,
+      rentPayer: alice.publicKey,
+      participantTokenAccount: carolTokenAccount.address,
+      tokenMint: tokenMint,
+      ...(await receiptAccounts(carol.publicKey)),
+    }).signers([carol]).rpc();
+
+    // (1e18 - 2,000 bonus tokens) * 2 / 3 plus the early investor bonus
+    expect((await getAccount(connection, carolTokenAccount.address)).amount.toString()).toEqual("666666666666666333");
+    expect((await getAccount(connection, (await receiptAccounts(carol.publicKey)).receiptTokenAccount)).amount.toString()).toEqual("0");
+    expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(alice.publicKey))).toBeNull();
+
+    expect((await getAccount(connection, await claim(bob))).amount.toString()).toEqual("333333333333333666");
+    expect((await getAccount(connection, (await receiptAccounts(bob.publicKey)).receiptTokenAccount)).amount.toString()).toEqual("0");
+  }, 50000 * 60);
+});
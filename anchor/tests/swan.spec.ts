@@ -1,20 +1,28 @@
 import * as anchor from "@coral-xyz/anchor";
 import { AnchorProvider, Program } from "@coral-xyz/anchor";
 import {
+  Ed25519Program,
   LAMPORTS_PER_SOL,
   PublicKey,
 } from "@solana/web3.js";
 import { Swan } from "../target/types/swan";
+import { getSwanAllowlistLeaf, getSwanAuditLog, getSwanIntentMessage, verifySwanAuditLog } from "../src/swan-exports";
+import { keccak_256 } from "@noble/hashes/sha3";
 import { ASSOCIATED_TOKEN_PROGRAM_ID, createAssociatedTokenAccount, createMint, getAccount, getAssociatedTokenAddress, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { createHash } from "crypto";
 const IDL = require("../target/idl/swan.json");
 
 const swanAddress = new PublicKey(
   "Dnu28pWdEj7C8NbZf8Yt9FuMSNG1c2djC5mDjqi6yMaV"
 );
 
-// This test currently runs directly with the local network cluster. 
-// Please remember to deploy the program to the local network while running the test.
-// To run the code please use anchor test --skip-local-validator  
+// Mock SOL/EUR price account loaded from tests/fixtures (see Anchor.toml), priced at 150 EUR
+const mockPriceOracle = new PublicKey(
+  "3VLrFdYX1uNegfERePqppskriXadHmhafhWT5E9BPUgr"
+);
+
+// This test runs on its own local validator through tests/Test.toml, next to the other suites under tests/.
+// To run the code please use anchor test
 describe("swan", () => {
   // Configure the client to use the local cluster.
   let swanProgram: Program<Swan>;
@@ -23,15 +31,38 @@ describe("swan", () => {
   const tokenProvider = anchor.web3.Keypair.generate();
   const Beneficiary  = anchor.web3.Keypair.generate();
   const safeGuard = anchor.web3.Keypair.generate();
+  const platform = anchor.web3.Keypair.generate();
+  const whitePaperHash = Array.from(createHash("sha256").update("white paper v1").digest());
   const stateWallet = anchor.web3.Keypair.generate();
 
 
   const participant = anchor.web3.Keypair.generate();
   const participant2 = anchor.web3.Keypair.generate();
   const participant3 = anchor.web3.Keypair.generate();
+  const participant3ColdWallet = anchor.web3.Keypair.generate();
+  const participant3Custodian = anchor.web3.Keypair.generate();
+  const participant4 = anchor.web3.Keypair.generate();
   
   let stateAta: any;
   let statePda: PublicKey;
+
+  // Priority round allowlist: participant 1 up to 0.5 SOL, participant 3 without an allocation limit
+  const participantLeaf = getSwanAllowlistLeaf(participant.publicKey, new anchor.BN(0.5 * LAMPORTS_PER_SOL));
+  const participant3Leaf = getSwanAllowlistLeaf(participant3.publicKey, new anchor.BN(0));
+  const merkleRoot = Array.from(keccak_256(Buffer.concat(
+    Buffer.compare(participantLeaf, participant3Leaf) <= 0
+      ? [participantLeaf, participant3Leaf]
+      : [participant3Leaf, participantLeaf]
+  )));
+
+  // Audited instruction used to check that the previous instruction saved the audit log head
+  const reconfigureDenylistAuthority = () =>
+    swanProgram.methods.configureDenylistAuthority(
+      tokenProvider.publicKey
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
   
   beforeAll(async () => {
     anchor.setProvider(anchor.AnchorProvider.env());
@@ -63,6 +94,8 @@ describe("swan", () => {
         tokenProvider.publicKey,
         Beneficiary.publicKey,
         safeGuard.publicKey,
+        0, // no platform fee
+        platform.publicKey,
       )
       .accounts({
         signer: tokenProvider.publicKey,
@@ -121,6 +154,92 @@ describe("swan", () => {
     expect(stateAccountInfo.tokenMint.toBase58()).toEqual(tokenMint.toBase58());
   },50000 * 60);
 
+  it("Should not activate the ico before the white paper is published", async () => {
+    await expect(
+      swanProgram.methods.activate(
+        new anchor.BN(10000000000)
+      ).accounts({
+         state: statePda,
+         tokenProvider: tokenProvider.publicKey,
+      }).signers([tokenProvider]).rpc()
+    ).rejects.toThrow(/WhitePaperMissing/);
+  });
+
+  it("Should publish the white paper", async () => {
+    const version = Buffer.alloc(2);
+    version.writeUInt16LE(1);
+    const [whitePaperPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("white_paper"), version],
+      swanProgram.programId
+    );
+
+    await swanProgram.methods.publishWhitePaper(
+      whitePaperHash,
+      "https://example.com/white-paper-v1.pdf"
+    ).accountsPartial({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+      whitePaper: whitePaperPda,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    expect(stateAccount.whitePaperVersion).toEqual(1);
+    expect(stateAccount.whitePaperHash).toEqual(whitePaperHash);
+
+    const whitePaper = await swanProgram.account.whitePaperVersion.fetch(whitePaperPda);
+    expect(whitePaper.uri).toEqual("https://example.com/white-paper-v1.pdf");
+  });
+
+  it("Should configure a EUR cap of 20,000 EUR", async () => {
+    await swanProgram.methods.configureEurCap(
+      new anchor.BN(2_000_000), // EUR cents
+      mockPriceOracle,
+      new anchor.BN(10 * 365 * 24 * 60 * 60), // the mock price has a fixed timestamp
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    expect(stateAccount.eurCapCents.toString()).toEqual("2000000");
+  });
+
+  it("Should configure staking with the local validator", async () => {
+    const { current } = await anchor.getProvider().connection.getVoteAccounts();
+    await swanProgram.methods.configureStaking(
+      new PublicKey(current[0].votePubkey)
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    expect(stateAccount.stakeVoteAccount.toBase58()).toEqual(current[0].votePubkey);
+  });
+
+  it("Should configure a priority round of 60 seconds for participants 1 and 3", async () => {
+    const priorityEnd = Math.floor(Date.now() / 1000) + 60;
+    await swanProgram.methods.configurePriorityRound(
+      merkleRoot,
+      new anchor.BN(priorityEnd),
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    expect(stateAccount.merkleRoot).toEqual(merkleRoot);
+    expect(stateAccount.priorityEnd.toNumber()).toEqual(priorityEnd);
+  });
+
   it("Should activate the ico", async () => {
     await swanProgram.methods.activate(
       new anchor.BN(10000000000)
@@ -135,6 +254,15 @@ describe("swan", () => {
     expect(stateAccount.participationActive).toEqual(true);
   });
 
+  it("Should not enable receipt tokens once the ico is active", async () => {
+    await expect(
+      swanProgram.methods.configureReceipts(false).accounts({
+        state: statePda,
+        tokenProvider: tokenProvider.publicKey,
+      }).signers([tokenProvider]).rpc()
+    ).rejects.toThrow(/AlreadyActivated/);
+  });
+
   it("should initialize participant 1 with 1 SOL ", async () => {
 
     // lets airdrop participant 
@@ -146,6 +274,7 @@ describe("swan", () => {
 
     await swanProgram.methods.initParticipant().accounts({
       participant: participant.publicKey,
+      payer: participant.publicKey,
     }).signers([participant]).rpc();
 
     const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
@@ -171,7 +300,8 @@ describe("swan", () => {
 
     await swanProgram.methods.initParticipant().accounts({
       participant: participant2.publicKey,
-    }).signers([participant2]).rpc();
+      payer: tokenProvider.publicKey, // sponsors the account rent
+    }).signers([participant2, tokenProvider]).rpc();
 
     const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
       [Buffer.from("participant"), participant2.publicKey.toBuffer()],
@@ -183,10 +313,10 @@ describe("swan", () => {
     )
     expect(participantAccount?.cancelled).toEqual(0);
     expect(participantAccount?.amount.toString()).toEqual("0");
-
+    expect(participantAccount?.rentPayer.toBase58()).toEqual(tokenProvider.publicKey.toBase58());
   });
 
-  it("Should initialize participant 3 with 200 SOL", async () => {
+  it("Should fund participant 3 with 200 SOL", async () => {
       
       // lets airdrop participant 
       const airdropSignature = await anchor.getProvider().connection.requestAirdrop(
@@ -194,24 +324,79 @@ describe("swan", () => {
         200* LAMPORTS_PER_SOL // Adjust based on the expected fees
       );
       await anchor.getProvider().connection.confirmTransaction(airdropSignature);
-  
-      await swanProgram.methods.initParticipant().accounts({
-        participant: participant3.publicKey,
-      }).signers([participant3]).rpc();
-  
+
+      // participant 3 has no participant account yet, `participate` creates it
       const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
         [Buffer.from("participant"), participant3.publicKey.toBuffer()],
         swanProgram.programId
       );
-  
       const participantAccount = await swanProgram.account.participantAccount.fetchNullable(
         participantAccountPublicKey
       )
-      expect(participantAccount?.cancelled).toEqual(0);
-      expect(participantAccount?.amount.toString()).toEqual("0");
+      expect(participantAccount).toBeNull();
   })
 
-  it("should allow participant (1) to participate with 0.5 SOL", async () => {
+  it("Should reject a denylisted wallet", async () => {
+    const sanctioned = anchor.web3.Keypair.generate();
+    const airdropSignature = await anchor.getProvider().connection.requestAirdrop(
+      sanctioned.publicKey,
+      LAMPORTS_PER_SOL // Adjust based on the expected fees
+    );
+    await anchor.getProvider().connection.confirmTransaction(airdropSignature);
+
+    await swanProgram.methods.configureDenylistAuthority(
+      tokenProvider.publicKey
+    ).accounts({
+      state: statePda,
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    await swanProgram.methods.addToDenylist(
+      sanctioned.publicKey
+    ).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await reconfigureDenylistAuthority();
+
+    await expect(
+      swanProgram.methods.initParticipant().accounts({
+        participant: sanctioned.publicKey,
+        payer: sanctioned.publicKey,
+      }).signers([sanctioned]).rpc()
+    ).rejects.toThrow(/AddressDenylisted/);
+  });
+
+  it("should keep the audit log writable after every denylist instruction", async () => {
+    const sanctioned = anchor.web3.Keypair.generate();
+    let stateAccount = await swanProgram.account.state.fetch(statePda);
+    const auditEntryCount = stateAccount.auditEntryCount.toNumber();
+
+    // each denylist instruction is followed by another audited instruction, which
+    // only succeeds if the denylist instruction saved the audit counter
+    await swanProgram.methods.addToDenylist(sanctioned.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await reconfigureDenylistAuthority();
+
+    await swanProgram.methods.allowDenylistedRefund(sanctioned.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await reconfigureDenylistAuthority();
+
+    await swanProgram.methods.removeFromDenylist(sanctioned.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    await reconfigureDenylistAuthority();
+
+    stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.auditEntryCount.toNumber()).toEqual(auditEntryCount + 6);
+  });
+
+  it("should allow participant (1) to participate with 0.5 SOL in the priority round", async () => {
     
     const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
       [Buffer.from("participant"), participant.publicKey.toBuffer()],
@@ -220,10 +405,15 @@ describe("swan", () => {
 
     await swanProgram.methods.participate(
       new anchor.BN(500000000), // 0.5 sol
+      new anchor.BN(0.5 * LAMPORTS_PER_SOL), // priority allocation
+      [Array.from(participant3Leaf)],
+      whitePaperHash, // accepted terms
     ).accountsPartial({
       state: statePda,
       participant: participant.publicKey,
+      payer: participant.publicKey,
       participantAccount: PPUBKEY,
+      priceOracle: mockPriceOracle,
     }).signers([participant]).rpc();
 
     const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
@@ -237,37 +427,33 @@ describe("swan", () => {
     expect(participantAccount.amount.toString()).toEqual("500000000");
     // the participant should be an early investor too. 
     expect(participantAccount.isEarlyInvestor).toEqual(1);
+    expect(participantAccount.acceptedTermsVersion).toEqual(1);
   });
 
-  it("should allow participant (2) to participate with 0.5 Sol", async () => {
-      
-      const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
-        [Buffer.from("participant"), participant2.publicKey.toBuffer()],
-        swanProgram.programId
-      );
-  
-      await swanProgram.methods.participate(
-        new anchor.BN(500000000), // 0.5 sol
+  it("should reject participation with outdated terms", async () => {
+    const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("participant"), participant.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    const outdatedHash = Array.from(createHash("sha256").update("white paper v0").digest());
+
+    await expect(
+      swanProgram.methods.participate(
+        new anchor.BN(100000000), // 0.1 sol
+        new anchor.BN(0), // no priority allocation
+        [],
+        outdatedHash,
       ).accountsPartial({
         state: statePda,
-        participant: participant2.publicKey,
+        participant: participant.publicKey,
+        payer: participant.publicKey,
         participantAccount: PPUBKEY,
-      }).signers([participant2]).rpc();
-  
-      const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
-        [Buffer.from("participant"), participant2.publicKey.toBuffer()],
-        swanProgram.programId
-      );
-  
-      const participantAccount = await swanProgram.account.participantAccount.fetch(
-        participantAccountPublicKey
-      )
-      expect(participantAccount.amount.toString()).toEqual("500000000");
-      // the participant should be an early investor too. 
-      expect(participantAccount.isEarlyInvestor).toEqual(1);
-    });
+        priceOracle: mockPriceOracle,
+      }).signers([participant]).rpc()
+    ).rejects.toThrow(/TermsMismatch/);
+  });
 
-  it("should allow participant (3) to participate with 100 SOL as a large investor", async () => {
+  it("should allow participant (3) to participate with 100 SOL as a large investor in the priority round", async () => {
         const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
           [Buffer.from("participant"), participant3.publicKey.toBuffer()],
           swanProgram.programId
@@ -275,10 +461,15 @@ describe("swan", () => {
         const OneHundreaDSol = 100 * LAMPORTS_PER_SOL
         await swanProgram.methods.participate(
           new anchor.BN(OneHundreaDSol), // 100 sol
+          new anchor.BN(0), // allowlisted without an allocation limit
+          [Array.from(participantLeaf)],
+          whitePaperHash, // accepted terms
         ).accountsPartial({
           state: statePda,
           participant: participant3.publicKey,
+          payer: participant3.publicKey,
           participantAccount: PPUBKEY,
+          priceOracle: mockPriceOracle,
         }).signers([participant3]).rpc();
     
         const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
@@ -299,11 +490,237 @@ describe("swan", () => {
         expect(stateAccount.largeInvestorCount.toNumber()).toEqual(1);
   });
 
-  it("Should check that the state account is updated correctly with total contribution of 3 participants", async () => {
+  it("should reject a wallet that is not allowlisted during the priority round", async () => {
+    // participant 2 replays the proof of participant 1
+    await expect(
+      swanProgram.methods.participate(
+        new anchor.BN(500000000),
+        new anchor.BN(0.5 * LAMPORTS_PER_SOL),
+        [Array.from(participant3Leaf)],
+        whitePaperHash,
+      ).accountsPartial({
+        state: statePda,
+        participant: participant2.publicKey,
+        payer: participant2.publicKey,
+        priceOracle: mockPriceOracle,
+      }).signers([participant2]).rpc()
+    ).rejects.toThrow(/NotAllowlisted/);
+  });
+
+  it("should reject participant (1) contributing beyond their priority allocation", async () => {
+    await expect(
+      swanProgram.methods.participate(
+        new anchor.BN(0.2 * LAMPORTS_PER_SOL),
+        new anchor.BN(0.5 * LAMPORTS_PER_SOL),
+        [Array.from(participant3Leaf)],
+        whitePaperHash,
+      ).accountsPartial({
+        state: statePda,
+        participant: participant.publicKey,
+        payer: participant.publicKey,
+        priceOracle: mockPriceOracle,
+      }).signers([participant]).rpc()
+    ).rejects.toThrow(/AllocationExceeded/);
+  });
+
+  it("should not transfer a position during the priority round", async () => {
+    await expect(
+      swanProgram.methods.transferPosition(
+        anchor.web3.Keypair.generate().publicKey
+      ).accounts({
+        state: statePda,
+        participant: participant.publicKey,
+        payer: participant.publicKey,
+        rentPayer: participant.publicKey,
+      }).signers([participant]).rpc()
+    ).rejects.toThrow(/PositionLocked/);
+  });
+
+  it("should allow participant (2) to participate with 0.5 Sol once the priority round has ended", async () => {
+      const { priorityEnd } = await swanProgram.account.state.fetch(statePda);
+      await delay(Math.max(0, (priorityEnd.toNumber() + 2) * 1000 - Date.now()));
+
+      const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("participant"), participant2.publicKey.toBuffer()],
+        swanProgram.programId
+      );
+  
+      await swanProgram.methods.participate(
+        new anchor.BN(500000000), // 0.5 sol
+        new anchor.BN(0), // no priority allocation
+        [],
+        whitePaperHash, // accepted terms
+      ).accountsPartial({
+        state: statePda,
+        participant: participant2.publicKey,
+        payer: participant2.publicKey,
+        participantAccount: PPUBKEY,
+        priceOracle: mockPriceOracle,
+      }).signers([participant2]).rpc();
+  
+      const [participantAccountPublicKey, _bump] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("participant"), participant2.publicKey.toBuffer()],
+        swanProgram.programId
+      );
+  
+      const participantAccount = await swanProgram.account.participantAccount.fetch(
+        participantAccountPublicKey
+      )
+      expect(participantAccount.amount.toString()).toEqual("500000000");
+      // the participant should be an early investor too. 
+      expect(participantAccount.isEarlyInvestor).toEqual(1);
+    }, 50000 * 60);
+
+  it("should reserve the contribution of participant (4) once denylisted", async () => {
+    const airdropSignature = await anchor.getProvider().connection.requestAirdrop(
+      participant4.publicKey,
+      11 * LAMPORTS_PER_SOL
+    );
+    await anchor.getProvider().connection.confirmTransaction(airdropSignature);
+
+    await swanProgram.methods.participate(
+      new anchor.BN(10 * LAMPORTS_PER_SOL),
+      new anchor.BN(0), // no priority allocation
+      [],
+      whitePaperHash, // accepted terms
+    ).accountsPartial({
+      state: statePda,
+      participant: participant4.publicKey,
+      payer: participant4.publicKey,
+      priceOracle: mockPriceOracle,
+    }).signers([participant4]).rpc();
+
+    await swanProgram.methods.addToDenylist(participant4.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const [denylistEntry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("denylist"), participant4.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    const entry = await swanProgram.account.denylistEntry.fetch(denylistEntry);
+    expect(entry.reservedLamports.toString()).toEqual("10000000000");
+    expect(entry.earlyInvestor).toEqual(true);
+    expect(entry.largeInvestor).toEqual(false);
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.totalDenylisted.toString()).toEqual("10000000000");
+    // its early investor bonus is no longer set aside
+    expect(stateAccount.activeEarlyInvestorCount.toNumber()).toEqual(3);
+
+    // the reserved contribution cannot be withdrawn by cancelling
+    await expect(
+      swanProgram.methods.cancel().accountsPartial({
+        state: statePda,
+        participant: participant4.publicKey,
+        participantAccount: PublicKey.findProgramAddressSync(
+          [Buffer.from("participant"), participant4.publicKey.toBuffer()],
+          swanProgram.programId
+        )[0],
+      }).signers([participant4]).rpc()
+    ).rejects.toThrow(/AddressDenylisted/);
+
+    // unlisting before distribution releases the reserve and sets the bonus aside again
+    await swanProgram.methods.removeFromDenylist(participant4.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    let restored = await swanProgram.account.state.fetch(statePda);
+    expect(restored.totalDenylisted.toString()).toEqual("0");
+    expect(restored.activeEarlyInvestorCount.toNumber()).toEqual(4);
+
+    await swanProgram.methods.addToDenylist(participant4.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+    restored = await swanProgram.account.state.fetch(statePda);
+    expect(restored.totalDenylisted.toString()).toEqual("10000000000");
+    expect(restored.activeEarlyInvestorCount.toNumber()).toEqual(3);
+  });
+
+  it("should reject participant (3) contributing beyond the EUR cap", async () => {
+    const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("participant"), participant3.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    // 111 SOL (16,650 EUR) contributed so far, 40 SOL more would be 22,650 EUR
+    await expect(
+      swanProgram.methods.participate(
+        new anchor.BN(40 * LAMPORTS_PER_SOL),
+        new anchor.BN(0), // no priority allocation
+        [],
+        whitePaperHash, // accepted terms
+      ).accountsPartial({
+        state: statePda,
+        participant: participant3.publicKey,
+        payer: participant3.publicKey,
+        participantAccount: PPUBKEY,
+        priceOracle: mockPriceOracle,
+      }).signers([participant3]).rpc()
+    ).rejects.toThrow(/EurCapExceeded/);
+
     const stateAccount = await swanProgram.account.state.fetch(
       statePda
     );
-    const totalConribution = (0.5 * LAMPORTS_PER_SOL) + (0.5 * LAMPORTS_PER_SOL) + (100 * LAMPORTS_PER_SOL);
+    expect(stateAccount.totalContributedEurCents.toString()).toEqual("1665000");
+  });
+
+  it("should apply the per-wallet cap to the owner of a funded participation", async () => {
+    // participant 3 already holds 100 SOL, so 151 SOL more would exceed the 250 SOL cap
+    await expect(
+      swanProgram.methods.participateFor(
+        new anchor.BN(151 * LAMPORTS_PER_SOL),
+        new anchor.BN(0),
+        [],
+        whitePaperHash,
+      ).accountsPartial({
+        state: statePda,
+        funder: tokenProvider.publicKey,
+        participant: participant3.publicKey,
+        priceOracle: mockPriceOracle,
+      }).signers([tokenProvider]).rpc()
+    ).rejects.toThrow(/MaxContributionExceeded/);
+  });
+
+  it("should reject expired or tampered participation intents", async () => {
+    const amount = new anchor.BN(LAMPORTS_PER_SOL);
+    const relayIntent = (signedAmount: anchor.BN, expiry: anchor.BN) => {
+      const message = getSwanIntentMessage(
+        statePda, participant3.publicKey, signedAmount, new anchor.BN(0), whitePaperHash, new anchor.BN(0), expiry, swanProgram.programId
+      );
+      return swanProgram.methods.participateWithIntent(
+        amount,
+        new anchor.BN(0),
+        [],
+        whitePaperHash,
+        new anchor.BN(0), // nonce
+        expiry,
+      ).accountsPartial({
+        state: statePda,
+        relayer: tokenProvider.publicKey,
+        participant: participant3.publicKey,
+        priceOracle: mockPriceOracle,
+      }).preInstructions([
+        Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: participant3.secretKey,
+          message,
+        }),
+      ]).signers([tokenProvider]).rpc();
+    };
+    const now = Math.floor(Date.now() / 1000);
+
+    await expect(relayIntent(amount, new anchor.BN(now - 60))).rejects.toThrow(/IntentExpired/);
+    // the participant signed for half the amount the relayer submits
+    await expect(
+      relayIntent(amount.divn(2), new anchor.BN(now + 3600))
+    ).rejects.toThrow(/InvalidIntent/);
+  });
+
+  it("Should check that the state account is updated correctly with total contribution of 4 participants", async () => {
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    const totalConribution = (0.5 * LAMPORTS_PER_SOL) + (0.5 * LAMPORTS_PER_SOL) + (100 * LAMPORTS_PER_SOL) + (10 * LAMPORTS_PER_SOL);
     expect(stateAccount.totalContributed.toString()).toEqual(totalConribution.toString());
     expect(stateAccount.activeEarlyInvestorCount.toNumber()).toEqual(3);
   });
@@ -330,19 +747,120 @@ describe("swan", () => {
     )
     expect(participantAccount.cancelled).toEqual(1);
     expect(participantAccount.isEarlyInvestor).toEqual(0);
+    // the cancelled amount is kept apart for the refund, the active amount can grow again
+    expect(participantAccount.amount.toString()).toEqual("0");
+    expect(participantAccount.cancelledAmount.toString()).toEqual("500000000");
 
     const stateAccount = await swanProgram.account.state.fetch(
       statePda
     );
     
     expect(stateAccount.totalCancelled.toString()).toEqual("500000000");
-    expect(stateAccount.totalContributed.toString()).toEqual("100500000000");
+    expect(stateAccount.totalContributed.toString()).toEqual("110500000000");
     expect(stateAccount.activeEarlyInvestorCount.toNumber()).toEqual(2);
   });
 
+  it("should not transfer a cancelled position", async () => {
+    await expect(
+      swanProgram.methods.transferPosition(
+        anchor.web3.Keypair.generate().publicKey
+      ).accounts({
+        state: statePda,
+        participant: participant2.publicKey,
+        payer: participant2.publicKey,
+        rentPayer: tokenProvider.publicKey,
+      }).signers([participant2]).rpc()
+    ).rejects.toThrow(/ParticipationCancelledAlready/);
+  });
+
+  it("should transfer the position of participant (3) to a new wallet and back", async () => {
+    const newWallet = anchor.web3.Keypair.generate();
+    const participantAddress = (wallet: PublicKey) => PublicKey.findProgramAddressSync(
+      [Buffer.from("participant"), wallet.toBuffer()],
+      swanProgram.programId
+    )[0];
+    const before = await swanProgram.account.participantAccount.fetch(participantAddress(participant3.publicKey));
+
+    await swanProgram.methods.transferPosition(newWallet.publicKey).accounts({
+      state: statePda,
+      participant: participant3.publicKey,
+      payer: participant3.publicKey,
+      rentPayer: participant3.publicKey,
+    }).signers([participant3]).rpc();
+
+    expect(await swanProgram.account.participantAccount.fetchNullable(participantAddress(participant3.publicKey))).toBeNull();
+    const moved = await swanProgram.account.participantAccount.fetch(participantAddress(newWallet.publicKey));
+    expect(moved.participant.toBase58()).toEqual(newWallet.publicKey.toBase58());
+    expect(moved.amount.toString()).toEqual(before.amount.toString());
+    expect(moved.participationTime.toString()).toEqual(before.participationTime.toString());
+    expect(moved.isEarlyInvestor).toEqual(before.isEarlyInvestor);
+    // the new owner has not accepted the terms
+    expect(before.acceptedTermsVersion).toEqual(1);
+    expect(moved.acceptedTermsVersion).toEqual(0);
+
+    // participant 3 sponsors the rent to move it back
+    await swanProgram.methods.transferPosition(participant3.publicKey).accounts({
+      state: statePda,
+      participant: newWallet.publicKey,
+      payer: participant3.publicKey,
+      rentPayer: participant3.publicKey,
+    }).signers([newWallet, participant3]).rpc();
+
+    const restored = await swanProgram.account.participantAccount.fetch(participantAddress(participant3.publicKey));
+    expect(restored.amount.toString()).toEqual(before.amount.toString());
+    expect(restored.rentPayer.toBase58()).toEqual(participant3.publicKey.toBase58());
+  });
+
+  it("Should stake 2 SOL and withdraw it back to the contract", async () => {
+    const stateBalance = await anchor.getProvider().connection.getBalance(statePda);
+    const stateBefore = await swanProgram.account.state.fetch(statePda);
+    await swanProgram.methods.stakeSafeguarded(
+      new anchor.BN(2 * LAMPORTS_PER_SOL)
+    ).accounts({
+      tokenProvider: tokenProvider.publicKey,
+      voteAccount: stateBefore.stakeVoteAccount,
+    }).signers([tokenProvider]).rpc();
+
+    let stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.stakeActive).toEqual(true);
+    expect(stateAccount.stakedLamports.toString()).toEqual("2000000000");
+
+    await swanProgram.methods.deactivateStake().accounts({
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    // stake deactivated in the epoch it was delegated can be withdrawn immediately
+    await swanProgram.methods.withdrawStake().accounts({
+      tokenProvider: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(stateAccount.stakeActive).toEqual(false);
+    expect(stateAccount.stakingRewards.toString()).toEqual("0");
+    expect(await anchor.getProvider().connection.getBalance(statePda)).toEqual(stateBalance);
+  });
+
   it("Should move 100.5 sol to safe guard account." , async () => {
+    const outsider = anchor.web3.Keypair.generate();
+    const airdropSignature = await anchor.getProvider().connection.requestAirdrop(
+      outsider.publicKey,
+      LAMPORTS_PER_SOL // Adjust based on the expected fees
+    );
+    await anchor.getProvider().connection.confirmTransaction(airdropSignature);
+    await expect(
+      swanProgram.methods.safeguard(
+        new anchor.BN(LAMPORTS_PER_SOL)
+      ).accountsPartial({
+        state: statePda,
+        tokenProvider: outsider.publicKey,
+        safeguardingAccount: safeGuard.publicKey,
+      }).signers([outsider]).rpc()
+    ).rejects.toThrow(/UnauthorizedCaller/);
+
     // lets call the safeguard function
-    await swanProgram.methods.safeguard().accountsPartial({
+    await swanProgram.methods.safeguard(
+      new anchor.BN(100.5 * LAMPORTS_PER_SOL)
+    ).accountsPartial({
       state: statePda,
       tokenProvider: tokenProvider.publicKey,
       safeguardingAccount: safeGuard.publicKey,
@@ -353,12 +871,33 @@ describe("swan", () => {
     expect(safeGuardAccount).toEqual(100.5 * LAMPORTS_PER_SOL);
 
     // lets check that the state account has been updated correctly and has 0.5 sol left for refund
+    // next to the 10 SOL reserved for participant 4
     const stateAccount = await swanProgram.account.state.fetch(
       statePda
     );
     expect(stateAccount.totalCancelled.toString()).toEqual("500000000");
+    expect(stateAccount.totalSafeguarded.toString()).toEqual("102500000000");
+    expect(stateAccount.totalReturnedFromSafeguard.toString()).toEqual("2000000000");
   })
 
+  it("should return 100.5 SOL from the safeguarding account before distribution", async () => {
+    const stateBalanceBefore = await anchor.getProvider().connection.getBalance(statePda);
+
+    await swanProgram.methods.returnFromSafeguard(
+      new anchor.BN(100.5 * LAMPORTS_PER_SOL)
+    ).accounts({
+      state: statePda,
+      safeguardingAccount: safeGuard.publicKey,
+    }).signers([safeGuard]).rpc();
+
+    const stateBalance = await anchor.getProvider().connection.getBalance(statePda);
+    expect(stateBalance).toEqual(stateBalanceBefore + 100.5 * LAMPORTS_PER_SOL);
+    const stateAccount = await swanProgram.account.state.fetch(
+      statePda
+    );
+    expect(stateAccount.totalReturnedFromSafeguard.toString()).toEqual("102500000000");
+  });
+
   it("should start destribution", async () => {
 
     // lets call end function now. 
@@ -377,7 +916,9 @@ describe("swan", () => {
   .distribute()
   .accounts({
     state: statePda, 
+    payer: Beneficiary.publicKey,
     beneficiary: Beneficiary.publicKey,
+    feeRecipient: platform.publicKey,
   })
   .signers([Beneficiary])
   .transaction();
@@ -386,17 +927,102 @@ describe("swan", () => {
   // lets confirm the signature 
   await anchor.getProvider().connection.confirmTransaction(signature);
 
-  // lets check the balance of the beneficiary account, it should be only 0.5 SOL
+  // lets check the balance of the beneficiary account, it should have received 100.5 SOL as the
+  // cancelled 0.5 SOL and the contribution of participant 4 are reserved for their refunds
   const beneficiaryAccount = await anchor.getProvider().connection.getBalance(Beneficiary.publicKey);
   // lets log how much benificiary account has in sol
-  expect((beneficiaryAccount/ LAMPORTS_PER_SOL).toFixed(2)).toEqual("1.50");
-  // lets check the state account now,  0.5 SOL left from the distribution
+  expect((beneficiaryAccount/ LAMPORTS_PER_SOL).toFixed(2)).toEqual("101.50");
+  // lets check the state account now, 10.5 SOL left from the distribution
   const stateAccount = await swanProgram.account.state.fetch(
     statePda
   );
   expect(stateAccount.totalCancelled.toString()).toEqual("500000000");
+  expect(stateAccount.feeLamports.toString()).toEqual("0");
+  expect(stateAccount.recipientLamports.toString()).toEqual("100500000000");
+  const rentExempt = await anchor.getProvider().connection.getMinimumBalanceForRentExemption(
+    (await anchor.getProvider().connection.getAccountInfo(statePda))!.data.length
+  );
+  expect(await anchor.getProvider().connection.getBalance(statePda)).toEqual(rentExempt + 10.5 * LAMPORTS_PER_SOL);
   },50000 * 60);
 
+  it("should not return safeguarded funds once distribution has started", async () => {
+    // nothing could pay out lamports returned after distribution
+    await expect(
+      swanProgram.methods.returnFromSafeguard(
+        new anchor.BN(1)
+      ).accounts({
+        state: statePda,
+        safeguardingAccount: safeGuard.publicKey,
+      }).signers([safeGuard]).rpc()
+    ).rejects.toThrow(/DistributionAlreadyStarted/);
+  });
+
+  it("should record administrative actions in a hash-chained audit log", async () => {
+    const entries = await getSwanAuditLog(swanProgram, statePda);
+    const actions = entries.map((entry) => Object.keys(entry.action)[0]);
+    expect(actions).toEqual([
+      "deposit",
+      "publishWhitePaper",
+      "configure",
+      "configure",
+      "configure",
+      "activate",
+      "configure",
+      "addToDenylist",
+      "configure",
+      "addToDenylist",
+      "configure",
+      "allowDenylistedRefund",
+      "configure",
+      "removeFromDenylist",
+      "configure",
+      "addToDenylist",
+      "removeFromDenylist",
+      "addToDenylist",
+      "stakeSafeguarded",
+      "deactivateStake",
+      "withdrawStake",
+      "safeguard",
+      "end",
+      "distribute",
+    ]);
+    expect(entries[0].amount.toString()).toEqual("1000000000000000000");
+    expect(verifySwanAuditLog(entries)).toEqual(-1);
+  });
+
+  it("should finalize the offer results", async () => {
+    await swanProgram.methods.finalize().accounts({
+      state: statePda,
+      payer: tokenProvider.publicKey,
+      tokenMint: tokenMint,
+    }).signers([tokenProvider]).rpc();
+
+    const [resultsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("results")],
+      swanProgram.programId
+    );
+    const results = await swanProgram.account.offerResults.fetch(resultsPda);
+    const stateAccount = await swanProgram.account.state.fetch(statePda);
+    expect(results.grossRaised.toString()).toEqual((111 * LAMPORTS_PER_SOL).toString());
+    expect(results.totalCancelled.toString()).toEqual("500000000");
+    // the cancelled 0.5 SOL and the 10 SOL reserved for participant 4
+    expect(results.totalRefunded.toString()).toEqual("10500000000");
+    expect(results.netToBeneficiary.toString()).toEqual(stateAccount.recipientLamports.toString());
+    expect(results.participantCount.toNumber()).toEqual(4);
+    // participants 1 and 3 are early investors and participant 3 a large one
+    expect(results.bonusTokens.toNumber()).toEqual(3000);
+    expect(stateAccount.finalized).toEqual(true);
+
+    // the results can only be written once
+    await expect(
+      swanProgram.methods.finalize().accounts({
+        state: statePda,
+        payer: tokenProvider.publicKey,
+        tokenMint: tokenMint,
+      }).signers([tokenProvider]).rpc()
+    ).rejects.toThrow();
+  });
+
   it("should allow participant (1) to claim", async () => {
     // lets create an associated token account for participant
     const participantATA = (await getOrCreateAssociatedTokenAccount(anchor.getProvider().connection, participant, tokenMint, participant.publicKey));
@@ -415,6 +1041,7 @@ describe("swan", () => {
       participantAccount: PPUBKEY,
       state: statePda,
       participant: participant.publicKey,
+      rentPayer: participant.publicKey,
       participantTokenAccount: participantATA.address,
       tokenMint: tokenMint,
     }).signers([participant]).rpc();
@@ -427,14 +1054,109 @@ describe("swan", () => {
     expect(closedAccount).toBeNull();
   });
 
-  it("should refund participant (2) with 0.5 SOL", async () => {
-    // lets send 1 extra sol to the contract to make sure the refund works
-    const airdropSignature = await anchor.getProvider().connection.requestAirdrop(
-      statePda,
-      LAMPORTS_PER_SOL 
+  it("should let participant (3) set a cold storage destination and a custodian", async () => {
+    await swanProgram.methods.setClaimOptions(
+      participant3ColdWallet.publicKey,
+      participant3Custodian.publicKey,
+    ).accounts({
+      participant: participant3.publicKey,
+    }).signers([participant3]).rpc();
+
+    const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("participant"), participant3.publicKey.toBuffer()],
+      swanProgram.programId
     );
-    await anchor.getProvider().connection.confirmTransaction(airdropSignature);
+    const participantAccount = await swanProgram.account.participantAccount.fetch(PPUBKEY);
+    expect(participantAccount.claimDestination.toBase58()).toEqual(participant3ColdWallet.publicKey.toBase58());
+    expect(participantAccount.claimDelegate.toBase58()).toEqual(participant3Custodian.publicKey.toBase58());
+
+    // only the custodian may claim on behalf of participant 3
+    const outsider = anchor.web3.Keypair.generate();
+    const participantATA = await getAssociatedTokenAddress(tokenMint, participant.publicKey);
+    await expect(
+      swanProgram.methods.delegatedClaim().accountsPartial({
+        state: statePda,
+        delegate: outsider.publicKey,
+        participant: participant3.publicKey,
+        rentPayer: participant3.publicKey,
+        participantTokenAccount: participantATA,
+        tokenMint: tokenMint,
+      }).signers([outsider]).rpc()
+    ).rejects.toThrow(/UnauthorizedCaller/);
+  });
+
+  it("should let anyone push the claim of participant (3) to their claim destination", async () => {
+    const participant3ATA = await getAssociatedTokenAddress(tokenMint, participant3ColdWallet.publicKey);
+    expect(await anchor.getProvider().connection.getAccountInfo(participant3ATA)).toBeNull();
 
+    const [PPUBKEY, _] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("participant"), participant3.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    const participant3BalanceBefore = await anchor.getProvider().connection.getBalance(participant3.publicKey);
+    const participantAccountRent = await anchor.getProvider().connection.getBalance(PPUBKEY);
+
+    // the caller is the provider wallet, participant 3 does not sign
+    await swanProgram.methods.pushClaim().accountsPartial({
+      state: statePda,
+      participant: participant3.publicKey,
+      rentPayer: participant3.publicKey,
+      tokenOwner: participant3ColdWallet.publicKey,
+      tokenMint: tokenMint,
+    }).rpc();
+
+    const tokenAccount = await getAccount(anchor.getProvider().connection, participant3ATA);
+    expect(tokenAccount.amount.toString()).toEqual("995024875621889562");
+    const closedAccount = await swanProgram.account.participantAccount.fetchNullable(PPUBKEY);
+    expect(closedAccount).toBeNull();
+    // the account rent went back to participant 3
+    const participant3Balance = await anchor.getProvider().connection.getBalance(participant3.publicKey);
+    expect(participant3Balance).toEqual(participant3BalanceBefore + participantAccountRent);
+  });
+
+  it("should skip settled and cancelled participants in a batch claim", async () => {
+    const connection = anchor.getProvider().connection;
+    const groupFor = async (wallet: PublicKey, rentPayer: PublicKey, tokenAccount: PublicKey) => {
+      const [participantAccount] = PublicKey.findProgramAddressSync(
+        [Buffer.from("participant"), wallet.toBuffer()],
+        swanProgram.programId
+      );
+      const [denylistEntry] = PublicKey.findProgramAddressSync(
+        [Buffer.from("denylist"), wallet.toBuffer()],
+        swanProgram.programId
+      );
+      return [participantAccount, wallet, rentPayer, tokenAccount, denylistEntry].map((pubkey) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: true,
+      }));
+    };
+    const participantATA = await getAssociatedTokenAddress(tokenMint, participant.publicKey);
+    const participant2ATA = (await getOrCreateAssociatedTokenAccount(connection, tokenProvider, tokenMint, participant2.publicKey)).address;
+
+    // a token account that does not belong to the participant is rejected
+    await expect(
+      swanProgram.methods.batchClaim().accounts({
+        state: statePda,
+        tokenMint: tokenMint,
+      }).remainingAccounts(await groupFor(participant2.publicKey, tokenProvider.publicKey, participantATA)).rpc()
+    ).rejects.toThrow(/InvalidBatch/);
+
+    // participant 1 already claimed and participant 2 cancelled, so nothing is paid
+    const participantBalance = (await getAccount(connection, participantATA)).amount;
+    await swanProgram.methods.batchClaim().accounts({
+      state: statePda,
+      tokenMint: tokenMint,
+    }).remainingAccounts([
+      ...(await groupFor(participant.publicKey, participant.publicKey, participantATA)),
+      ...(await groupFor(participant2.publicKey, tokenProvider.publicKey, participant2ATA)),
+    ]).rpc();
+
+    expect((await getAccount(connection, participantATA)).amount).toEqual(participantBalance);
+    expect((await getAccount(connection, participant2ATA)).amount.toString()).toEqual("0");
+  });
+
+  it("should refund participant (2) with 0.5 SOL", async () => {
     // get current wallet balance of participant 2
     const participant2BalanceBeforeClaim = await anchor.getProvider().connection.getBalance(participant2.publicKey);
 
@@ -449,6 +1171,8 @@ describe("swan", () => {
       [tokenMint.toBuffer()],
       swanProgram.programId
     );
+    const sponsorBalanceBeforeClaim = await anchor.getProvider().connection.getBalance(tokenProvider.publicKey);
+    const participantAccountRent = await anchor.getProvider().connection.getBalance(PPUBKEY);
     const transaction = 
     await swanProgram.methods.claim(
       programTokenAccountBump 
@@ -456,6 +1180,7 @@ describe("swan", () => {
       participantAccount: PPUBKEY,
       state: statePda,
       participant: participant2.publicKey,
+      rentPayer: tokenProvider.publicKey, // sponsored the account rent
       participantTokenAccount: participant2ATA.address,
       tokenMint: tokenMint,
     }).signers([participant2]).rpc();
@@ -463,6 +1188,52 @@ describe("swan", () => {
     const participant2Balance = await anchor.getProvider().connection.getBalance(participant2.publicKey);
    // the expect should remove some fees so lets assume its about 0.1 SOL 
    expect(participant2Balance).toBeGreaterThan(participant2BalanceBeforeClaim + (0.5 * LAMPORTS_PER_SOL) - (0.1 * LAMPORTS_PER_SOL));
+   // the account rent goes back to the sponsor
+   const sponsorBalance = await anchor.getProvider().connection.getBalance(tokenProvider.publicKey);
+   expect(sponsorBalance).toEqual(sponsorBalanceBeforeClaim + participantAccountRent);
+  });
+
+  it("should refund the reserved contribution of participant (4) once allowed", async () => {
+    const connection = anchor.getProvider().connection;
+    const [PPUBKEY] = PublicKey.findProgramAddressSync(
+      [Buffer.from("participant"), participant4.publicKey.toBuffer()],
+      swanProgram.programId
+    );
+    const participant4ATA = await getOrCreateAssociatedTokenAccount(connection, participant4, tokenMint, participant4.publicKey);
+    const [, programTokenAccountBump] = PublicKey.findProgramAddressSync(
+      [tokenMint.toBuffer()],
+      swanProgram.programId
+    );
+    const claim = () => swanProgram.methods.claim(programTokenAccountBump).accountsPartial({
+      participantAccount: PPUBKEY,
+      state: statePda,
+      participant: participant4.publicKey,
+      rentPayer: participant4.publicKey,
+      participantTokenAccount: participant4ATA.address,
+      tokenMint: tokenMint,
+    }).signers([participant4]).rpc();
+
+    await expect(claim()).rejects.toThrow(/AddressDenylisted/);
+    // the reserved contribution was paid to nobody, so the wallet cannot be unlisted anymore
+    await expect(
+      swanProgram.methods.removeFromDenylist(participant4.publicKey).accounts({
+        state: statePda,
+        denylistAuthority: tokenProvider.publicKey,
+      }).signers([tokenProvider]).rpc()
+    ).rejects.toThrow(/DistributionAlreadyStarted/);
+
+    await swanProgram.methods.allowDenylistedRefund(participant4.publicKey).accounts({
+      state: statePda,
+      denylistAuthority: tokenProvider.publicKey,
+    }).signers([tokenProvider]).rpc();
+
+    const stateBalanceBefore = await connection.getBalance(statePda);
+    await claim();
+
+    // the 10 SOL come out of the reserve, not out of the other participants' refunds
+    expect(await connection.getBalance(statePda)).toEqual(stateBalanceBefore - 10 * LAMPORTS_PER_SOL);
+    expect(await swanProgram.account.participantAccount.fetchNullable(PPUBKEY)).toBeNull();
+    expect((await getAccount(connection, participant4ATA.address)).amount.toString()).toEqual("0");
   });
 });
 